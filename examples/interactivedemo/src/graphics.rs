@@ -12,7 +12,7 @@ use cgmath::conv::{array2, array3};
 use cgmath::{Array, EuclideanSpace, Point2, Point3, Vector2, Vector3};
 use glium;
 use glium::{Display, DrawParameters, Program, Surface, VertexBuffer};
-use spade::rtree::{RTree, RTreeNode};
+use spade::rtree::{RTree, RTreeNodeRef};
 use spade::{BoundingRect, HasPosition};
 
 const VERTEX_SHADER_SRC: &str = r#"
@@ -214,15 +214,15 @@ pub fn get_color_for_depth(depth: usize) -> [f32; 3] {
 fn get_tree_edges(tree: &RTree<Point2<f64>>, buffer: &mut Vec<Vertex>) -> Vec<Vertex> {
     let mut vertices = Vec::new();
     let vertex_color = Point3::new(0.0, 0.0, 1.0);
-    let mut to_visit = vec![tree.root()];
+    let mut to_visit: Vec<_> = tree.root_node().into_iter().collect();
     while let Some(cur) = to_visit.pop() {
-        for child in cur.children().iter() {
+        for child in cur.children() {
             match child {
-                RTreeNode::Leaf(point) => vertices.push(Vertex::new(
+                RTreeNodeRef::Leaf(point) => vertices.push(Vertex::new(
                     array2(point.to_vec().cast().unwrap()),
                     array3(vertex_color),
                 )),
-                RTreeNode::DirectoryNode(ref data) => {
+                RTreeNodeRef::Directory(data) => {
                     to_visit.push(data);
                     push_rectangle(buffer, &data.mbr(), get_color_for_depth(data.depth()));
                 }
@@ -16,7 +16,7 @@ use num::zero;
 use rand::distributions::range::SampleRange;
 use rand::distributions::{Distribution, Range};
 use rand::{SeedableRng, XorShiftRng};
-use spade::rtree::RTree;
+use spade::rtree::{RTree, RTreeOptions, SplitStrategy};
 use spade::SpadeNum;
 use std::fs::File;
 use std::io::{stdout, Write};
@@ -24,6 +24,7 @@ use std::path::Path;
 use std::time::{Duration, Instant};
 
 fn main() {
+    run_compare_split_strategies();
     run_compare_operations_bench();
 }
 
@@ -47,6 +48,51 @@ fn duration_ns(duration: Duration) -> u32 {
     duration.as_secs() as u32 * 1_000_000_000 + duration.subsec_nanos()
 }
 
+fn run_compare_split_strategies() {
+    const NUM_VERTICES: usize = 200000;
+    const ITERATIONS: usize = 40000;
+
+    let vertices = random_points_with_seed::<f32>(NUM_VERTICES, b"split.me.in.half");
+    let query_points = random_points_with_seed(ITERATIONS, b"or.maybe.thirds?");
+
+    println!("Comparing split strategies...");
+    for &strategy in &[
+        SplitStrategy::RStar,
+        SplitStrategy::Quadratic,
+        SplitStrategy::Linear,
+    ] {
+        for &forced_reinsertion in &[true, false] {
+            let options = RTreeOptions::new()
+                .split_strategy(strategy)
+                .forced_reinsertion(forced_reinsertion);
+            let mut tree = RTree::new_with_options(options);
+            let mut insert_time = Vec::new();
+            let mut nearest_neighbor_time = Vec::new();
+            measure(&mut insert_time, &vertices, |point| tree.insert(point));
+            measure(&mut nearest_neighbor_time, &query_points, |point| {
+                tree.nearest_neighbor(&point)
+            });
+            let statistics = tree.statistics_for_queries(&query_points);
+            println!(
+                "{:?}, forced reinsertion: {}: insert {} ns, nearest neighbor {} ns, \
+                 overlap: {:.3}, node accesses: {:.2}, fill factor: {:.3}",
+                strategy,
+                forced_reinsertion,
+                insert_time[0],
+                nearest_neighbor_time[0],
+                statistics.total_overlap,
+                statistics.average_node_accesses,
+                statistics.average_fill_factor,
+            );
+        }
+    }
+    let statistics = RTree::bulk_load(vertices).statistics_for_queries(&query_points);
+    println!(
+        "Bulk loaded: overlap: {:.3}, node accesses: {:.2}, fill factor: {:.3}",
+        statistics.total_overlap, statistics.average_node_accesses, statistics.average_fill_factor,
+    );
+}
+
 fn run_compare_operations_bench() {
     const MAX_VERTICES: usize = 4000000;
     const ITERATIONS: usize = 40000;
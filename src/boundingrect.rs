@@ -6,10 +6,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::misc::max_inline;
+use crate::misc::{max_inline, min_inline};
 use crate::point_traits::{PointN, PointNExtensions};
-use crate::traits::SpatialObject;
-use num::{one, zero, Signed};
+use crate::primitives::SimpleEdge;
+use crate::traits::{SpadeFloat, SpatialObject};
+use num::{one, zero, Float, Signed};
 
 /// An axis aligned minimal bounding rectangle.
 ///
@@ -153,6 +154,18 @@ where
             && self.upper.all_comp_wise(&other.lower(), |l, r| l >= r)
     }
 
+    /// Returns the squared minimal distance between this and another bounding rectangle.
+    ///
+    /// Returns zero if both rectangles intersect.
+    pub fn min_dist2_rect(&self, other: &BoundingRect<V>) -> V::Scalar {
+        let gap = other
+            .lower
+            .sub(&self.upper)
+            .max_point(&self.lower.sub(&other.upper))
+            .max_point(&V::new());
+        gap.length2()
+    }
+
     #[doc(hidden)]
     pub fn min_point(&self, point: &V) -> V {
         self.upper.min_point(&self.lower.max_point(&point))
@@ -201,6 +214,56 @@ where
     }
 }
 
+impl<V> BoundingRect<V>
+where
+    V: PointN,
+    V::Scalar: SpadeFloat,
+{
+    /// Checks if an edge intersects this bounding rectangle.
+    ///
+    /// Returns `true` if any point of the edge lies within the rectangle, including its border.
+    pub fn intersects_edge(&self, edge: &SimpleEdge<V>) -> bool {
+        let direction = edge.to.sub(&edge.from);
+        self.clip_ray(&edge.from, &direction, one()).is_some()
+    }
+
+    /// Returns the parameter at which a ray enters this bounding rectangle.
+    ///
+    /// The ray consists of all points `origin + t * direction` with `t >= 0`. The smallest
+    /// such `t` that lies within the rectangle is returned, or `None` if the ray misses
+    /// the rectangle. Returns zero if `origin` is contained.
+    pub fn ray_entry(&self, origin: &V, direction: &V) -> Option<V::Scalar> {
+        self.clip_ray(origin, direction, V::Scalar::infinity())
+    }
+
+    /// Intersects a ray with this rectangle's slabs, only considering `0 <= t <= max_t`.
+    fn clip_ray(&self, origin: &V, direction: &V, max_t: V::Scalar) -> Option<V::Scalar> {
+        let mut t_min = zero();
+        let mut t_max = max_t;
+        for i in 0..V::dimensions() {
+            let o = *origin.nth(i);
+            let d = *direction.nth(i);
+            let lower = *self.lower.nth(i);
+            let upper = *self.upper.nth(i);
+            if d == zero() {
+                // The ray is parallel to this slab
+                if o < lower || o > upper {
+                    return None;
+                }
+            } else {
+                let t1 = (lower - o) / d;
+                let t2 = (upper - o) / d;
+                t_min = max_inline(t_min, min_inline(t1, t2));
+                t_max = min_inline(t_max, max_inline(t1, t2));
+                if t_min > t_max {
+                    return None;
+                }
+            }
+        }
+        Some(t_min)
+    }
+}
+
 impl<V> SpatialObject for BoundingRect<V>
 where
     V: PointN,
@@ -223,6 +286,7 @@ where
 #[cfg(test)]
 mod test {
     use super::BoundingRect;
+    use crate::primitives::SimpleEdge;
     use crate::traits::SpatialObject;
 
     #[test]
@@ -250,4 +314,45 @@ mod test {
         assert_eq!(rect.distance2(&[2.0, 2.0]), 2.0);
         assert_eq!(rect.distance2(&[2.0, 0.5]), 1.0);
     }
+
+    #[test]
+    fn test_bounding_rect_min_dist2_rect() {
+        let rect = BoundingRect {
+            lower: [0.0, 0.0],
+            upper: [1.0, 1.0],
+        };
+        let touching = BoundingRect::from_corners(&[1.0, 0.5], &[2.0, 2.0]);
+        let overlapping = BoundingRect::from_corners(&[-1.0, 0.5], &[0.5, 0.7]);
+        let separated = BoundingRect::from_corners(&[3.0, -3.0], &[4.0, -2.0]);
+        assert_eq!(rect.min_dist2_rect(&rect), 0.0);
+        assert_eq!(rect.min_dist2_rect(&touching), 0.0);
+        assert_eq!(rect.min_dist2_rect(&overlapping), 0.0);
+        assert_eq!(rect.min_dist2_rect(&separated), 8.0);
+        assert_eq!(separated.min_dist2_rect(&rect), 8.0);
+    }
+
+    #[test]
+    fn test_bounding_rect_intersects_edge() {
+        let rect = BoundingRect::from_corners(&[0.0, 0.0], &[2.0, 1.0]);
+        let edge = |from, to| SimpleEdge::new(from, to);
+        assert!(rect.intersects_edge(&edge([0.5, 0.5], [0.7, 0.7])));
+        assert!(rect.intersects_edge(&edge([-1.0, 0.5], [3.0, 0.5])));
+        assert!(rect.intersects_edge(&edge([-1.0, -1.0], [0.0, 0.0])));
+        assert!(rect.intersects_edge(&edge([1.0, 2.0], [1.0, -2.0])));
+        assert!(rect.intersects_edge(&edge([-1.0, 1.0], [3.0, 1.0])));
+        assert!(!rect.intersects_edge(&edge([-1.0, 0.5], [-0.1, 0.5])));
+        assert!(!rect.intersects_edge(&edge([-1.0, 0.0], [0.0, 2.0])));
+        assert!(!rect.intersects_edge(&edge([3.0, 2.0], [3.0, -2.0])));
+    }
+
+    #[test]
+    fn test_bounding_rect_ray_entry() {
+        let rect = BoundingRect::from_corners(&[0.0, 0.0], &[2.0, 1.0]);
+        assert_eq!(rect.ray_entry(&[-1.0, 0.5], &[1.0, 0.0]), Some(1.0));
+        assert_eq!(rect.ray_entry(&[-1.0, 0.5], &[2.0, 0.0]), Some(0.5));
+        assert_eq!(rect.ray_entry(&[1.0, 0.5], &[1.0, 3.0]), Some(0.0));
+        assert_eq!(rect.ray_entry(&[1.0, -2.0], &[0.0, 1.0]), Some(2.0));
+        assert_eq!(rect.ray_entry(&[-1.0, 0.5], &[-1.0, 0.0]), None);
+        assert_eq!(rect.ray_entry(&[-1.0, 2.0], &[1.0, 0.0]), None);
+    }
 }
@@ -0,0 +1,374 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Support for geographic coordinates.
+//!
+//! Treating longitude and latitude as planar coordinates yields wrong results near the
+//! poles and across the antimeridian. This module contains a geographic point type that
+//! can be inserted into an `RTree` together with the `Haversine` metric, which measures
+//! great-circle distances on a sphere. Use it with the r-tree's metric queries, e.g.
+//! `RTree::nearest_neighbor_with_metric` or `RTree::lookup_in_circle_with_metric`.
+//!
+//! # Example
+//! ```
+//! extern crate spade;
+//!
+//! use spade::geo::{GeoPoint, Haversine};
+//! use spade::rtree::RTree;
+//!
+//! fn main() {
+//!     let mut tree = RTree::new();
+//!     tree.insert(GeoPoint::new(179.5, 0.0));
+//!     tree.insert(GeoPoint::new(177.0, 0.0));
+//!     let query = GeoPoint::new(-179.5, 0.0);
+//!     // Across the antimeridian, (179.5, 0.0) is only one degree away
+//!     let nearest = tree.nearest_neighbor_with_metric(&query, Haversine::earth());
+//!     assert_eq!(nearest, Some(&GeoPoint::new(179.5, 0.0)));
+//!     // All points within 200 kilometers
+//!     let close = tree.lookup_in_circle_with_metric(&query, &200_000.0, Haversine::earth());
+//!     assert_eq!(close.len(), 1);
+//! }
+//! ```
+
+use crate::boundingrect::BoundingRect;
+use crate::misc::{max_inline, min_inline};
+use crate::point_traits::PointN;
+use crate::rtree::{Metric, RTree, SelectionFunction, SelectionIterator};
+use crate::traits::{HasPosition, SpadeFloat, SpadeNum, SpatialObject};
+use num::{zero, Float, NumCast};
+
+#[cfg(feature = "serde_serialize")]
+use serde::{Deserialize, Serialize};
+
+/// A geographic position given by longitude and latitude in degrees.
+///
+/// Longitudes should lie within [-180, 180], latitudes within [-90, 90].
+/// The longitude is the point's first, the latitude its second dimension.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+pub struct GeoPoint<S: SpadeNum> {
+    /// The longitude in degrees.
+    pub lon: S,
+    /// The latitude in degrees.
+    pub lat: S,
+}
+
+impl<S: SpadeNum> GeoPoint<S> {
+    /// Creates a new point from its longitude and latitude, given in degrees.
+    pub fn new(lon: S, lat: S) -> Self {
+        GeoPoint { lon, lat }
+    }
+}
+
+impl<S: SpadeNum + Copy> PointN for GeoPoint<S> {
+    type Scalar = S;
+
+    fn dimensions() -> usize {
+        2
+    }
+
+    fn nth(&self, index: usize) -> &S {
+        match index {
+            0 => &self.lon,
+            1 => &self.lat,
+            _ => panic!("Index out of bounds for GeoPoint: {}", index),
+        }
+    }
+
+    fn nth_mut(&mut self, index: usize) -> &mut S {
+        match index {
+            0 => &mut self.lon,
+            1 => &mut self.lat,
+            _ => panic!("Index out of bounds for GeoPoint: {}", index),
+        }
+    }
+
+    fn from_value(value: S) -> Self {
+        GeoPoint::new(value, value)
+    }
+}
+
+/// The great-circle distance on a sphere, calculated with the haversine formula.
+///
+/// Distances are given in the unit of the sphere's radius, e.g. in meters for
+/// `Haversine::earth()`. The metric is supported for all point like objects
+/// positioned with a `GeoPoint`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Haversine<S> {
+    radius: S,
+}
+
+impl<S: SpadeFloat> Haversine<S> {
+    /// Creates a new metric for a sphere with a given radius.
+    pub fn new(radius: S) -> Self {
+        Haversine { radius }
+    }
+
+    /// Creates a new metric using the earth's mean radius, measuring distances in meters.
+    pub fn earth() -> Self {
+        Haversine::new(<S as NumCast>::from(6_371_008.8).unwrap())
+    }
+
+    /// Returns the great-circle distance between two points.
+    pub fn distance_between(&self, from: &GeoPoint<S>, to: &GeoPoint<S>) -> S {
+        self.radius * central_angle(from, to)
+    }
+
+    fn min_distance_to_rect(&self, rect: &BoundingRect<GeoPoint<S>>, point: &GeoPoint<S>) -> S {
+        let lower = rect.lower();
+        let upper = rect.upper();
+        if lower.lon <= point.lon && point.lon <= upper.lon {
+            // The closest point lies on the same meridian
+            let delta = max_inline(
+                max_inline(lower.lat - point.lat, point.lat - upper.lat),
+                zero(),
+            );
+            return self.radius * delta.to_radians();
+        }
+        // Otherwise, the closest point lies on one of the rectangle's meridian edges
+        let west = meridian_angle(point, lower.lon, lower.lat, upper.lat);
+        let east = meridian_angle(point, upper.lon, lower.lat, upper.lat);
+        self.radius * min_inline(west, east)
+    }
+}
+
+/// Returns the central angle between two points in radians.
+fn central_angle<S: SpadeFloat>(from: &GeoPoint<S>, to: &GeoPoint<S>) -> S {
+    let two: S = <S as NumCast>::from(2.0).unwrap();
+    let (lat1, lat2) = (from.lat.to_radians(), to.lat.to_radians());
+    let half_delta_lat = (lat2 - lat1) / two;
+    let half_delta_lon = (to.lon - from.lon).to_radians() / two;
+    let a = half_delta_lat.sin().powi(2) + lat1.cos() * lat2.cos() * half_delta_lon.sin().powi(2);
+    two * Float::min(a.sqrt(), S::one()).asin()
+}
+
+/// Returns the smallest central angle between a point and a meridian segment.
+fn meridian_angle<S: SpadeFloat>(point: &GeoPoint<S>, lon: S, lat_min: S, lat_max: S) -> S {
+    let full_circle: S = <S as NumCast>::from(360.0).unwrap();
+    let half_circle: S = <S as NumCast>::from(180.0).unwrap();
+    let quarter_circle: S = <S as NumCast>::from(90.0).unwrap();
+    let mut delta_lon = (point.lon - lon).abs() % full_circle;
+    if delta_lon > half_circle {
+        delta_lon = full_circle - delta_lon;
+    }
+    if delta_lon < quarter_circle {
+        // Latitude of the point on the meridian's great circle that is closest to `point`
+        let lat = point.lat.to_radians();
+        let closest_lat = (lat.tan() / delta_lon.to_radians().cos())
+            .atan()
+            .to_degrees();
+        if lat_min <= closest_lat && closest_lat <= lat_max {
+            return (lat.cos() * delta_lon.to_radians().sin()).asin();
+        }
+    }
+    // The distance is monotonic along the segment, the closest point is one of its ends
+    let lower = central_angle(point, &GeoPoint::new(lon, lat_min));
+    let upper = central_angle(point, &GeoPoint::new(lon, lat_max));
+    min_inline(lower, upper)
+}
+
+impl<S, T> Metric<T> for Haversine<S>
+where
+    S: SpadeFloat,
+    T: HasPosition<Point = GeoPoint<S>>,
+{
+    fn distance(&self, object: &T, point: &GeoPoint<S>) -> S {
+        self.distance_between(&object.position(), point)
+    }
+
+    fn min_distance(&self, rect: &BoundingRect<GeoPoint<S>>, point: &GeoPoint<S>) -> S {
+        self.min_distance_to_rect(rect, point)
+    }
+}
+
+/// Selects all objects intersecting a geographic rectangle.
+///
+/// If the rectangle's western longitude is larger than its eastern longitude, the
+/// rectangle wraps around the antimeridian.
+/// Used by `RTree::lookup_in_geo_rectangle`.
+pub struct SelectInGeoRectangle<S: SpadeNum> {
+    south_west: GeoPoint<S>,
+    north_east: GeoPoint<S>,
+}
+
+impl<S: SpadeNum + Copy> SelectInGeoRectangle<S> {
+    /// Creates a selection function for the rectangle spanned by its south western and
+    /// north eastern corner.
+    pub fn new(south_west: GeoPoint<S>, north_east: GeoPoint<S>) -> Self {
+        SelectInGeoRectangle {
+            south_west,
+            north_east,
+        }
+    }
+
+    fn intersects(&self, mbr: &BoundingRect<GeoPoint<S>>) -> bool {
+        let (lower, upper) = (mbr.lower(), mbr.upper());
+        if lower.lat > self.north_east.lat || upper.lat < self.south_west.lat {
+            return false;
+        }
+        let (west, east) = (self.south_west.lon, self.north_east.lon);
+        if west <= east {
+            lower.lon <= east && upper.lon >= west
+        } else {
+            // The rectangle consists of [west, 180] and [-180, east]
+            upper.lon >= west || lower.lon <= east
+        }
+    }
+}
+
+impl<S, T> SelectionFunction<T> for SelectInGeoRectangle<S>
+where
+    S: SpadeNum + Copy,
+    T: SpatialObject<Point = GeoPoint<S>>,
+{
+    fn should_descend(&self, mbr: &BoundingRect<GeoPoint<S>>) -> bool {
+        self.intersects(mbr)
+    }
+
+    fn matches(&self, object: &T) -> bool {
+        self.intersects(&object.mbr())
+    }
+}
+
+impl<S, T> RTree<T>
+where
+    S: SpadeNum + Copy,
+    T: SpatialObject<Point = GeoPoint<S>>,
+{
+    /// Returns an iterator over all objects (partially) contained in a geographic
+    /// rectangle.
+    ///
+    /// The rectangle is spanned from its south western to its north eastern corner.
+    /// A western longitude larger than the eastern longitude denotes a rectangle that
+    /// crosses the antimeridian, e.g. `west = 170.0` and `east = -170.0` select a band
+    /// that is 20 degrees wide.
+    pub fn lookup_in_geo_rectangle(
+        &self,
+        south_west: &GeoPoint<S>,
+        north_east: &GeoPoint<S>,
+    ) -> SelectionIterator<'_, T, SelectInGeoRectangle<S>> {
+        self.locate_with_selection_function(SelectInGeoRectangle::new(*south_west, *north_east))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GeoPoint, Haversine};
+    use crate::boundingrect::BoundingRect;
+    use crate::rtree::{Metric, RTree};
+    use crate::testutils::*;
+
+    fn random_geo_points(size: usize, seed: &[u8; 16]) -> Vec<GeoPoint<f64>> {
+        random_points_with_seed::<f64>(size, seed)
+            .into_iter()
+            .map(|p| GeoPoint::new(p.x * 180.0, p.y * 90.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_haversine_distance() {
+        let metric = Haversine::new(1.0);
+        let a = GeoPoint::new(0.0, 0.0);
+        let b = GeoPoint::new(90.0, 0.0);
+        let pole = GeoPoint::new(-45.0, 90.0);
+        let eps = 1e-12;
+        assert_relative_eq!(
+            metric.distance_between(&a, &b),
+            ::std::f64::consts::FRAC_PI_2,
+            epsilon = eps
+        );
+        assert_relative_eq!(
+            metric.distance_between(&a, &pole),
+            ::std::f64::consts::FRAC_PI_2,
+            epsilon = eps
+        );
+        assert_relative_eq!(
+            metric.distance_between(&GeoPoint::new(179.0, 0.0), &GeoPoint::new(-179.0, 0.0)),
+            2.0f64.to_radians(),
+            epsilon = eps
+        );
+        // Paris to New York
+        let paris = GeoPoint::new(2.3522, 48.8566);
+        let new_york = GeoPoint::new(-74.0060, 40.7128);
+        let distance = Haversine::earth().distance_between(&paris, &new_york);
+        assert!((distance - 5_837_000.0f64).abs() < 5_000.0);
+    }
+
+    #[test]
+    fn test_haversine_lower_bound() {
+        let metric = Haversine::new(1.0);
+        let points = random_geo_points(400, b"Round and round ");
+        let queries = random_geo_points(50, b"the globe we go!");
+        for chunk in points.chunks(4) {
+            let rect = BoundingRect::from_points(chunk.iter().cloned());
+            for query in &queries {
+                let bound = Metric::<GeoPoint<f64>>::min_distance(&metric, &rect, query);
+                for point in chunk {
+                    assert!(bound <= metric.distance(point, query) + 1e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_geo_nearest_neighbor() {
+        let metric = Haversine::earth();
+        let points = random_geo_points(1000, b"Where is the nea");
+        let tree = RTree::bulk_load(points.clone());
+        let mut queries = random_geo_points(30, b"rest town, dear?");
+        queries.push(GeoPoint::new(180.0, 0.0));
+        queries.push(GeoPoint::new(0.0, 90.0));
+        queries.push(GeoPoint::new(-179.9, -89.9));
+        for query in &queries {
+            let expected = points
+                .iter()
+                .map(|p| metric.distance(p, query))
+                .fold(f64::INFINITY, f64::min);
+            let nearest = tree.nearest_neighbor_with_metric(query, metric).unwrap();
+            assert_eq!(metric.distance(nearest, query), expected);
+
+            let radius = 1_000_000.0;
+            let in_radius = tree.lookup_in_circle_with_metric(query, &radius, metric);
+            let expected = points
+                .iter()
+                .filter(|p| metric.distance(*p, query) < radius)
+                .count();
+            assert_eq!(in_radius.len(), expected);
+        }
+    }
+
+    #[test]
+    fn test_geo_rectangle_wraparound() {
+        let points = random_geo_points(1000, b"Crossing the dat");
+        let tree = RTree::bulk_load(points.clone());
+        let south_west = GeoPoint::new(150.0, -30.0);
+        let north_east = GeoPoint::new(-160.0, 40.0);
+        let mut result: Vec<_> = tree
+            .lookup_in_geo_rectangle(&south_west, &north_east)
+            .cloned()
+            .collect();
+        let mut expected: Vec<_> = points
+            .iter()
+            .filter(|p| (p.lon >= 150.0 || p.lon <= -160.0) && p.lat >= -30.0 && p.lat <= 40.0)
+            .cloned()
+            .collect();
+        assert!(!expected.is_empty());
+        result.sort_by(|l, r| l.partial_cmp(r).unwrap());
+        expected.sort_by(|l, r| l.partial_cmp(r).unwrap());
+        assert_eq!(result, expected);
+
+        let regular: Vec<_> = tree
+            .lookup_in_geo_rectangle(&GeoPoint::new(-10.0, -10.0), &GeoPoint::new(10.0, 10.0))
+            .collect();
+        let expected = points
+            .iter()
+            .filter(|p| p.lon.abs() <= 10.0 && p.lat.abs() <= 10.0)
+            .count();
+        assert_eq!(regular.len(), expected);
+    }
+}
@@ -18,8 +18,10 @@
 //!   * Can be backed up by an r*-tree to improve performance when inserting randomly distributed points
 //!   * Supports vertex removal
 //! * A 2D constrained Delaunay triangulation: `spade::delaunay::ConstrainedDelaunayTriangulation`
+//! * Geographic coordinates with great-circle distances: `spade::geo::GeoPoint`
 //! * Supports serde. Activate the `serde_serialize` feature to enable (de)serialization of r*-trees,
 //! (constrained) Delaunay triangulations and primitives.
+//! * Supports parallel bulk loading and batch queries of r*-trees. Activate the `rayon` feature to enable.
 //!
 //! # Supported point types
 //! Spade works well with points from the `nalgebra` and `cgmath` packages. Also, fixed size arrays of size 2, 3 and 4 are
@@ -40,6 +42,9 @@ extern crate num;
 extern crate pdqselect;
 extern crate smallvec;
 
+#[cfg(feature = "rayon")]
+extern crate rayon;
+
 #[cfg(feature = "serde_serialize")]
 #[macro_use]
 extern crate serde;
@@ -64,6 +69,7 @@ mod point_traits;
 mod traits;
 
 pub mod delaunay;
+pub mod geo;
 pub mod kernels;
 pub mod primitives;
 pub mod rtree;
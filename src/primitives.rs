@@ -13,11 +13,11 @@
 //! implementing `SpatialObject`.
 
 use crate::boundingrect::BoundingRect;
-use crate::kernels::{DelaunayKernel, TrivialKernel};
+use crate::kernels::{DelaunayKernel, FloatKernel, TrivialKernel};
 use crate::point_traits::{PointN, PointNExtensions, TwoDimensional};
 use crate::traits::{SpadeFloat, SpadeNum, SpatialObject};
 use cgmath::{One, Point3, Zero};
-use num::{one, zero, Float, Signed};
+use num::{one, zero, Float, FromPrimitive, Signed};
 
 #[cfg(feature = "serde_serialize")]
 use serde::{Deserialize, Serialize};
@@ -449,9 +449,207 @@ where
     }
 }
 
+/// A simple polygon with optional holes.
+///
+/// The polygon's exterior and each of its holes are given as rings of vertices. A ring is
+/// closed implicitly, its last vertex must not repeat the first one. The rings must not
+/// intersect themselves or each other, and all holes must lie within the exterior.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+pub struct SimplePolygon<V: PointN> {
+    exterior: Vec<V>,
+    holes: Vec<Vec<V>>,
+}
+
+impl<V> SimplePolygon<V>
+where
+    V: PointN,
+{
+    /// Creates a new polygon without holes.
+    ///
+    /// # Panics
+    /// Panics if `exterior` contains less than three vertices.
+    pub fn new(exterior: Vec<V>) -> SimplePolygon<V> {
+        SimplePolygon::with_holes(exterior, Vec::new())
+    }
+
+    /// Creates a new polygon with holes.
+    ///
+    /// # Panics
+    /// Panics if the exterior or any hole contains less than three vertices.
+    pub fn with_holes(exterior: Vec<V>, holes: Vec<Vec<V>>) -> SimplePolygon<V> {
+        assert!(
+            exterior.len() >= 3 && holes.iter().all(|hole| hole.len() >= 3),
+            "A polygon ring needs at least three vertices"
+        );
+        SimplePolygon { exterior, holes }
+    }
+
+    /// Returns the vertices of the polygon's exterior.
+    pub fn exterior(&self) -> &[V] {
+        &self.exterior
+    }
+
+    /// Returns the vertices of all holes.
+    pub fn holes(&self) -> &[Vec<V>] {
+        &self.holes
+    }
+
+    /// Returns all edges of the exterior and the holes.
+    pub fn edges(&self) -> impl Iterator<Item = SimpleEdge<V>> + '_ {
+        ::std::iter::once(&self.exterior)
+            .chain(self.holes.iter())
+            .flat_map(|ring| ring_edges(ring))
+    }
+}
+
+fn ring_edges<V: PointN>(ring: &[V]) -> impl Iterator<Item = SimpleEdge<V>> + '_ {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(from, to)| SimpleEdge::new(from.clone(), to.clone()))
+}
+
+/// Returns the doubled signed area of a ring, positive if it is ordered counterclockwise.
+fn ring_double_area<V: TwoDimensional>(ring: &[V]) -> V::Scalar {
+    ring_edges(ring).fold(zero(), |area: V::Scalar, edge| {
+        area + edge.from.nth(0).clone() * edge.to.nth(1).clone()
+            - edge.to.nth(0).clone() * edge.from.nth(1).clone()
+    })
+}
+
+/// Returns the winding number of a ring around a point, or `None` if the point lies on
+/// the ring.
+fn ring_winding_number<K, V>(ring: &[V], point: &V) -> Option<i32>
+where
+    K: DelaunayKernel<V::Scalar>,
+    V: TwoDimensional,
+{
+    let mut winding_number = 0;
+    for edge in ring_edges(ring) {
+        let side = K::side_query(&edge, point);
+        if side.is_on_line()
+            && BoundingRect::from_corners(&edge.from, &edge.to).contains_point(point)
+        {
+            return None;
+        }
+        if *edge.from.nth(1) <= *point.nth(1) {
+            if *edge.to.nth(1) > *point.nth(1) && side.is_on_left_side() {
+                winding_number += 1;
+            }
+        } else if *edge.to.nth(1) <= *point.nth(1) && side.is_on_right_side() {
+            winding_number -= 1;
+        }
+    }
+    Some(winding_number)
+}
+
+impl<V> SimplePolygon<V>
+where
+    V: TwoDimensional,
+{
+    /// Returns the polygon's doubled signed area.
+    ///
+    /// The area is positive if the exterior is ordered counterclockwise and negative
+    /// otherwise. The holes' areas are subtracted, regardless of their orientation.
+    pub fn signed_double_area(&self) -> V::Scalar {
+        let exterior = ring_double_area(&self.exterior);
+        let holes = self.holes.iter().fold(zero(), |area: V::Scalar, hole| {
+            area + ring_double_area(hole).abs()
+        });
+        if exterior.is_negative() {
+            exterior + holes
+        } else {
+            exterior - holes
+        }
+    }
+
+    /// Returns `true` if the polygon's exterior is ordered counterclockwise.
+    pub fn is_ordered_ccw(&self) -> bool {
+        ring_double_area(&self.exterior).is_positive()
+    }
+
+    /// Checks if a point lies within the polygon or on its boundary.
+    ///
+    /// Only the kernel's `side_query` and exact comparisons of coordinates are used. Thus,
+    /// the result is exact for any kernel that calculates `side_query` exactly, e.g.
+    /// `FloatKernel`. Points within a hole are not contained, points on a hole's
+    /// boundary are.
+    pub fn contains_point<K>(&self, point: &V) -> bool
+    where
+        K: DelaunayKernel<V::Scalar>,
+    {
+        match ring_winding_number::<K, V>(&self.exterior, point) {
+            None => return true,
+            Some(0) => return false,
+            _ => {}
+        }
+        // Points on a hole's boundary have no winding number and are contained
+        self.holes
+            .iter()
+            .all(|hole| ring_winding_number::<K, V>(hole, point).unwrap_or(0) == 0)
+    }
+}
+
+impl<V> SimplePolygon<V>
+where
+    V: PointN,
+    V::Scalar: SpadeFloat,
+{
+    /// Returns the polygon's signed area, see `signed_double_area`.
+    pub fn signed_area(&self) -> V::Scalar
+    where
+        V: TwoDimensional,
+    {
+        self.signed_double_area() / (one::<V::Scalar>() + one())
+    }
+
+    /// Returns the squared distance of a point to the nearest edge of the exterior
+    /// or of any hole.
+    ///
+    /// Unlike `distance2`, this distance is not zero for points within the polygon.
+    pub fn boundary_distance2(&self, point: &V) -> V::Scalar {
+        self.edges()
+            .map(|edge| edge.distance2(point))
+            .fold(None, |min: Option<V::Scalar>, distance| match min {
+                Some(min) if min <= distance => Some(min),
+                _ => Some(distance),
+            })
+            .unwrap()
+    }
+}
+
+impl<V> SpatialObject for SimplePolygon<V>
+where
+    V: TwoDimensional,
+    V::Scalar: SpadeFloat + FromPrimitive,
+{
+    type Point = V;
+
+    fn mbr(&self) -> BoundingRect<V> {
+        let mut result = BoundingRect::from_point(self.exterior[0].clone());
+        for vertex in &self.exterior[1..] {
+            result.add_point(vertex.clone());
+        }
+        result
+    }
+
+    fn distance2(&self, point: &V) -> V::Scalar {
+        if self.contains(point) {
+            zero()
+        } else {
+            self.boundary_distance2(point)
+        }
+    }
+
+    fn contains(&self, point: &V) -> bool {
+        self.contains_point::<FloatKernel>(point)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{SimpleCircle, SimpleEdge, SimpleTriangle};
+    use super::{SimpleCircle, SimpleEdge, SimplePolygon, SimpleTriangle};
+    use crate::boundingrect::BoundingRect;
     use crate::kernels::{FloatKernel, TrivialKernel};
     use crate::traits::SpatialObject;
     use cgmath::{Point2, Point3};
@@ -582,4 +780,120 @@ mod test {
         assert_eq!(c.contains(&p2), false);
         assert_eq!(c.contains(&p3), false);
     }
+
+    fn l_shape_with_hole() -> SimplePolygon<Point2<i64>> {
+        // An L shaped polygon with a triangular hole (1, 1), (1, 2), (2, 1)
+        let exterior = vec![
+            Point2::new(0, 0),
+            Point2::new(6, 0),
+            Point2::new(6, 2),
+            Point2::new(2, 2),
+            Point2::new(2, 6),
+            Point2::new(0, 6),
+        ];
+        let hole = vec![Point2::new(1, 1), Point2::new(1, 2), Point2::new(2, 1)];
+        SimplePolygon::with_holes(exterior, vec![hole])
+    }
+
+    #[test]
+    fn test_polygon_area() {
+        let polygon = l_shape_with_hole();
+        // 20 for the L shape minus 0.5 for the hole
+        assert_eq!(polygon.signed_double_area(), 39);
+        assert!(polygon.is_ordered_ccw());
+        let mut exterior = polygon.exterior().to_vec();
+        exterior.reverse();
+        let reversed = SimplePolygon::with_holes(exterior, polygon.holes().to_vec());
+        assert_eq!(reversed.signed_double_area(), -39);
+        assert!(!reversed.is_ordered_ccw());
+
+        let square = SimplePolygon::new(vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(0.0, 2.0),
+            Point2::new(2.0, 2.0),
+            Point2::new(2.0, 0.0),
+        ]);
+        assert_eq!(square.signed_area(), -4.0);
+    }
+
+    #[test]
+    fn test_polygon_contains() {
+        let polygon = l_shape_with_hole();
+        let contains = |x, y| polygon.contains_point::<TrivialKernel>(&Point2::new(x, y));
+        // Interior
+        assert!(contains(4, 1));
+        assert!(contains(1, 4));
+        // Boundary and vertices
+        assert!(contains(3, 0));
+        assert!(contains(2, 4));
+        assert!(contains(6, 2));
+        assert!(contains(2, 2));
+        // Outside, also at the height of a vertex
+        assert!(!contains(4, 4));
+        assert!(!contains(7, 2));
+        assert!(!contains(-1, 2));
+        assert!(!contains(-1, 6));
+        assert!(!contains(3, -1));
+        // The hole's boundary is contained
+        assert!(contains(1, 1));
+        assert!(contains(2, 1));
+
+        let hole = SimplePolygon::new(vec![
+            Point2::new(1.0, 1.0),
+            Point2::new(1.0, 3.0),
+            Point2::new(3.0, 3.0),
+            Point2::new(3.0, 1.0),
+        ]);
+        let square = SimplePolygon::with_holes(
+            vec![
+                Point2::new(0.0, 0.0),
+                Point2::new(4.0, 0.0),
+                Point2::new(4.0, 4.0),
+                Point2::new(0.0, 4.0),
+            ],
+            vec![hole.exterior().to_vec()],
+        );
+        assert!(!square.contains_point::<FloatKernel>(&Point2::new(2.0, 2.0)));
+        assert!(square.contains_point::<FloatKernel>(&Point2::new(0.5, 2.0)));
+        assert!(square.contains_point::<FloatKernel>(&Point2::new(1.0, 2.0)));
+        // A point very close to, but not on the boundary
+        assert!(!square.contains_point::<FloatKernel>(&Point2::new(4.0 + 1e-15, 2.0)));
+        assert!(hole.contains_point::<FloatKernel>(&Point2::new(2.0, 2.0)));
+    }
+
+    #[test]
+    fn test_polygon_spatial_object() {
+        use crate::rtree::RTree;
+
+        let square = |x: f64, y: f64| {
+            SimplePolygon::new(vec![
+                Point2::new(x, y),
+                Point2::new(x + 1.0, y),
+                Point2::new(x + 1.0, y + 1.0),
+                Point2::new(x, y + 1.0),
+            ])
+        };
+        let polygon = square(0.0, 0.0);
+        assert_eq!(
+            polygon.mbr(),
+            BoundingRect::from_corners(&Point2::new(0.0, 0.0), &Point2::new(1.0, 1.0))
+        );
+        assert_eq!(polygon.distance2(&Point2::new(0.5, 0.5)), 0.0);
+        assert_eq!(polygon.boundary_distance2(&Point2::new(0.5, 0.25)), 0.0625);
+        assert_eq!(polygon.distance2(&Point2::new(3.0, 1.0)), 4.0);
+        assert_eq!(polygon.distance2(&Point2::new(2.0, 2.0)), 2.0);
+
+        let mut tree = RTree::new();
+        for x in 0..10 {
+            for y in 0..10 {
+                tree.insert(square(x as f64 * 2.0, y as f64 * 2.0));
+            }
+        }
+        assert_eq!(tree.lookup(&Point2::new(4.5, 6.5)), Some(&square(4.0, 6.0)));
+        assert_eq!(tree.lookup(&Point2::new(5.5, 6.5)), None);
+        assert_eq!(
+            tree.nearest_neighbor(&Point2::new(5.6, 6.5)),
+            Some(&square(6.0, 6.0))
+        );
+    }
 }
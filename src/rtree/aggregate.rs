@@ -0,0 +1,411 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{DirectoryNodeData, RTree, RTreeNode};
+use crate::boundingrect::BoundingRect;
+use crate::traits::SpatialObject;
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// Defines a value that an r-tree aggregates over all objects of each subtree.
+///
+/// The aggregate of a set of objects is obtained by combining the values of all
+/// objects, starting with `identity`. This requires a commutative monoid:
+/// `combine` must be associative and commutative and `identity` must be its neutral
+/// element. Count, sum, minimum and maximum are common examples.
+///
+/// Use `RTree::set_aggregator` to store aggregates in a tree and
+/// `RTree::aggregate_in_rectangle` to query them.
+///
+/// # Example
+/// ```
+/// extern crate cgmath;
+/// extern crate spade;
+///
+/// use cgmath::Point2;
+/// use spade::BoundingRect;
+/// use spade::rtree::{Aggregator, RTree};
+///
+/// // Sums up the x coordinates of all points
+/// struct SumX;
+///
+/// impl Aggregator<Point2<f64>> for SumX {
+///     type Value = f64;
+///
+///     fn identity(&self) -> f64 {
+///         0.0
+///     }
+///
+///     fn value(&self, point: &Point2<f64>) -> f64 {
+///         point.x
+///     }
+///
+///     fn combine(&self, first: &f64, second: &f64) -> f64 {
+///         first + second
+///     }
+/// }
+///
+/// fn main() {
+///     let mut tree = RTree::new();
+///     tree.set_aggregator(SumX);
+///     for x in 0..100 {
+///         tree.insert(Point2::new(x as f64, 0.0));
+///     }
+///     let rect = BoundingRect::from_corners(&Point2::new(-0.5, -1.0), &Point2::new(9.5, 1.0));
+///     assert_eq!(tree.aggregate_in_rectangle::<SumX>(&rect), Some(45.0));
+/// }
+/// ```
+pub trait Aggregator<T>: Send + Sync + 'static {
+    /// The aggregated value.
+    type Value: Clone + Send + Sync + 'static;
+
+    /// Returns the aggregate of an empty set of objects.
+    fn identity(&self) -> Self::Value;
+
+    /// Returns the value of a single object.
+    fn value(&self, object: &T) -> Self::Value;
+
+    /// Combines two aggregates.
+    fn combine(&self, first: &Self::Value, second: &Self::Value) -> Self::Value;
+}
+
+/// Counts objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Count;
+
+impl<T> Aggregator<T> for Count {
+    type Value = usize;
+
+    fn identity(&self) -> usize {
+        0
+    }
+
+    fn value(&self, _: &T) -> usize {
+        1
+    }
+
+    fn combine(&self, first: &usize, second: &usize) -> usize {
+        first + second
+    }
+}
+
+type AggregateValue = Box<dyn Any + Send + Sync>;
+
+/// Object safe version of `Aggregator`, operating on type erased values.
+pub(super) trait ErasedAggregator<T>: Send + Sync
+where
+    T: SpatialObject,
+{
+    fn as_any(&self) -> &dyn Any;
+
+    fn aggregate_children(&self, children: &[RTreeNode<T>]) -> AggregateValue;
+
+    fn add_node(&self, value: &mut AggregateValue, node: &RTreeNode<T>);
+
+    fn clone_value(&self, value: &AggregateValue) -> AggregateValue;
+}
+
+impl<T, A> ErasedAggregator<T> for A
+where
+    T: SpatialObject,
+    A: Aggregator<T>,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn aggregate_children(&self, children: &[RTreeNode<T>]) -> AggregateValue {
+        let mut result = self.identity();
+        for child in children {
+            result = self.combine(&result, &node_value(self, child));
+        }
+        Box::new(result)
+    }
+
+    fn add_node(&self, value: &mut AggregateValue, node: &RTreeNode<T>) {
+        let value = value.downcast_mut::<A::Value>().unwrap();
+        *value = self.combine(value, &node_value(self, node));
+    }
+
+    fn clone_value(&self, value: &AggregateValue) -> AggregateValue {
+        Box::new(value.downcast_ref::<A::Value>().unwrap().clone())
+    }
+}
+
+fn node_value<T, A>(aggregator: &A, node: &RTreeNode<T>) -> A::Value
+where
+    T: SpatialObject,
+    A: Aggregator<T>,
+{
+    match node {
+        RTreeNode::Leaf(ref t) => aggregator.value(t),
+        RTreeNode::DirectoryNode(ref data) => stored_value::<T, A>(data).clone(),
+    }
+}
+
+fn stored_value<T, A>(node: &DirectoryNodeData<T>) -> &A::Value
+where
+    T: SpatialObject,
+    A: Aggregator<T>,
+{
+    node.aggregate
+        .as_ref()
+        .and_then(|aggregate| aggregate.value.downcast_ref())
+        .expect("Directory node is missing its aggregate")
+}
+
+/// The aggregate stored in a directory node.
+pub(super) struct NodeAggregate<T>
+where
+    T: SpatialObject,
+{
+    aggregator: Arc<dyn ErasedAggregator<T>>,
+    value: AggregateValue,
+}
+
+impl<T> Clone for NodeAggregate<T>
+where
+    T: SpatialObject,
+{
+    fn clone(&self) -> Self {
+        NodeAggregate {
+            aggregator: self.aggregator.clone(),
+            value: self.aggregator.clone_value(&self.value),
+        }
+    }
+}
+
+impl<T> fmt::Debug for NodeAggregate<T>
+where
+    T: SpatialObject,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NodeAggregate")
+    }
+}
+
+impl<T> DirectoryNodeData<T>
+where
+    T: SpatialObject,
+{
+    pub(super) fn aggregator(&self) -> Option<Arc<dyn ErasedAggregator<T>>> {
+        self.aggregate
+            .as_ref()
+            .map(|aggregate| aggregate.aggregator.clone())
+    }
+
+    /// Sets the aggregator of this subtree and recalculates all aggregates.
+    pub(super) fn set_aggregator(&mut self, aggregator: Option<Arc<dyn ErasedAggregator<T>>>) {
+        for child in &mut self.children {
+            if let RTreeNode::DirectoryNode(ref mut data) = child {
+                data.set_aggregator(aggregator.clone());
+            }
+        }
+        self.set_node_aggregator(aggregator);
+    }
+
+    /// Sets the aggregator of this node only and calculates its aggregate from the
+    /// node's children, which must already store their own aggregates.
+    pub(super) fn set_node_aggregator(&mut self, aggregator: Option<Arc<dyn ErasedAggregator<T>>>) {
+        self.aggregate = aggregator.map(|aggregator| NodeAggregate {
+            value: aggregator.aggregate_children(&self.children),
+            aggregator,
+        });
+    }
+
+    /// Recalculates the aggregate from the node's children.
+    pub(super) fn update_aggregate(&mut self) {
+        if let Some(ref mut aggregate) = self.aggregate {
+            aggregate.value = aggregate.aggregator.aggregate_children(&self.children);
+        }
+    }
+
+    /// Adds a node that is inserted into this subtree to the aggregate.
+    pub(super) fn add_to_aggregate(&mut self, node: &RTreeNode<T>) {
+        if let Some(ref mut aggregate) = self.aggregate {
+            aggregate.aggregator.add_node(&mut aggregate.value, node);
+        }
+    }
+
+    fn aggregate_in_rectangle<A>(
+        &self,
+        aggregator: &A,
+        rect: &BoundingRect<T::Point>,
+        result: &mut A::Value,
+    ) where
+        A: Aggregator<T>,
+    {
+        for child in &self.children {
+            match child {
+                RTreeNode::Leaf(ref t) => {
+                    if t.mbr().intersects(rect) {
+                        *result = aggregator.combine(result, &aggregator.value(t));
+                    }
+                }
+                RTreeNode::DirectoryNode(ref data) => {
+                    let mbr = data.mbr();
+                    if rect.contains_rect(&mbr) {
+                        *result = aggregator.combine(result, stored_value::<T, A>(data));
+                    } else if rect.intersects(&mbr) {
+                        data.aggregate_in_rectangle(aggregator, rect, result);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject,
+{
+    /// Stores the aggregate of each subtree in the tree's directory nodes.
+    ///
+    /// The aggregates are calculated in `O(n)` time and kept up to date by all
+    /// operations that add or remove objects. Replaces any previously set aggregator.
+    /// Mutable references returned by the tree, e.g. by `iter_mut`, must not be used to
+    /// change an object's aggregated value. Aggregators are not serialized.
+    pub fn set_aggregator<A>(&mut self, aggregator: A)
+    where
+        A: Aggregator<T>,
+    {
+        self.root.set_aggregator(Some(Arc::new(aggregator)));
+    }
+
+    /// Removes the tree's aggregator and all stored aggregates.
+    pub fn clear_aggregator(&mut self) {
+        self.root.set_aggregator(None);
+    }
+
+    /// Aggregates all objects (partially) contained in a rectangle.
+    ///
+    /// Selects the same objects as `lookup_in_rectangle`. Subtrees whose bounding box
+    /// lies completely within the rectangle are not visited, instead their stored
+    /// aggregate is used. Returns `None` if the tree's aggregator, set by
+    /// `set_aggregator`, is not of type `A`.
+    pub fn aggregate_in_rectangle<A>(&self, query_rect: &BoundingRect<T::Point>) -> Option<A::Value>
+    where
+        A: Aggregator<T>,
+    {
+        let aggregate = self.root.aggregate.as_ref()?;
+        let aggregator = aggregate.aggregator.as_any().downcast_ref::<A>()?;
+        let mut result = aggregator.identity();
+        if self.size > 0 {
+            self.root
+                .aggregate_in_rectangle(aggregator, query_rect, &mut result);
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Aggregator, Count};
+    use crate::boundingrect::BoundingRect;
+    use crate::rtree::RTree;
+    use crate::testutils::*;
+    use cgmath::Point2;
+
+    struct MaxData;
+
+    impl Aggregator<PointWithData> for MaxData {
+        type Value = Option<u32>;
+
+        fn identity(&self) -> Option<u32> {
+            None
+        }
+
+        fn value(&self, object: &PointWithData) -> Option<u32> {
+            Some(object.data)
+        }
+
+        fn combine(&self, first: &Option<u32>, second: &Option<u32>) -> Option<u32> {
+            ::std::cmp::max(*first, *second)
+        }
+    }
+
+    fn check_aggregates<T: crate::traits::SpatialObject<Point = Point2<f32>>>(
+        tree: &RTree<T>,
+        rects: &[BoundingRect<Point2<f32>>],
+    ) {
+        for rect in rects {
+            assert_eq!(
+                tree.aggregate_in_rectangle::<Count>(rect),
+                Some(tree.lookup_in_rectangle(rect).len())
+            );
+        }
+    }
+
+    fn query_rects() -> Vec<BoundingRect<Point2<f32>>> {
+        let corners = random_points_with_seed::<f32>(40, b"Sum it all up!!!");
+        let mut rects: Vec<_> = corners
+            .chunks(2)
+            .map(|c| BoundingRect::from_corners(&c[0], &c[1]))
+            .collect();
+        rects.push(BoundingRect::from_corners(
+            &Point2::new(-2.0, -2.0),
+            &Point2::new(2.0, 2.0),
+        ));
+        rects
+    }
+
+    #[test]
+    fn test_aggregate_count() {
+        let rects = query_rects();
+        let (mut tree, points) = create_random_tree::<f32>(3000, b"Count me in, ple");
+        assert_eq!(tree.aggregate_in_rectangle::<Count>(&rects[0]), None);
+        tree.set_aggregator(Count);
+        check_aggregates(&tree, &rects);
+
+        for point in random_points_with_seed::<f32>(1000, b"More, more, more") {
+            tree.insert(point);
+        }
+        check_aggregates(&tree, &rects);
+        for point in points.iter().step_by(2) {
+            assert!(tree.remove(point));
+        }
+        check_aggregates(&tree, &rects);
+        for point in points.iter().skip(1).step_by(4) {
+            tree.update(point, Point2::new(point.x * 0.5, point.y));
+        }
+        check_aggregates(&tree, &rects);
+        tree.drain_in_rectangle(&rects[1]);
+        check_aggregates(&tree, &rects);
+        tree.lookup_and_remove(&points[1]);
+        check_aggregates(&tree, &rects);
+
+        let mut bulk_loaded = RTree::bulk_load(points.clone());
+        bulk_loaded.set_aggregator(Count);
+        check_aggregates(&bulk_loaded, &rects);
+        let cloned = bulk_loaded.clone();
+        check_aggregates(&cloned, &rects);
+        bulk_loaded.clear_aggregator();
+        assert_eq!(bulk_loaded.aggregate_in_rectangle::<Count>(&rects[0]), None);
+
+        tree.retain(|_| false);
+        assert_eq!(tree.aggregate_in_rectangle::<Count>(&rects[0]), Some(0));
+    }
+
+    #[test]
+    fn test_aggregate_max() {
+        let points = random_points_with_seed::<f32>(1000, b"Who is the bigge");
+        let mut tree = RTree::new();
+        tree.set_aggregator(MaxData);
+        for (index, point) in points.iter().enumerate() {
+            tree.insert(PointWithData::new(*point, index as u32));
+        }
+        for rect in query_rects() {
+            let expected = tree.lookup_in_rectangle(&rect).iter().map(|p| p.data).max();
+            assert_eq!(
+                tree.aggregate_in_rectangle::<MaxData>(&rect),
+                Some(expected)
+            );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{DirectoryNodeData, RTree, RTreeNode, RTreeNodeDistanceWrapper};
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+use num::{one, zero};
+use std::collections::BinaryHeap;
+
+/// An iterator over the approximate nearest neighbors of a point.
+///
+/// This `struct` is created by the `approximate_nearest_neighbor_iterator` method on
+/// `RTree`. Each item is a tuple of an object and its actual squared distance to the
+/// query point.
+///
+/// A directory node is only expanded once its minimal distance, multiplied by
+/// `(1 + epsilon)²`, is not larger than the distance of the next object. Thus, the
+/// distance of each yielded object is at most `(1 + epsilon)²` times the squared
+/// distance of any object that was not yet yielded, while fewer nodes are visited
+/// than for an exact search.
+pub struct ApproximateNearestNeighborIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    nodes: BinaryHeap<RTreeNodeDistanceWrapper<'a, T>>,
+    query_point: T::Point,
+    factor: <T::Point as PointN>::Scalar,
+}
+
+impl<'a, T> ApproximateNearestNeighborIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    fn new(
+        root: &'a DirectoryNodeData<T>,
+        query_point: T::Point,
+        epsilon: <T::Point as PointN>::Scalar,
+    ) -> Self {
+        assert!(epsilon >= zero(), "Epsilon must not be negative");
+        let base = epsilon + one();
+        let mut result = ApproximateNearestNeighborIterator {
+            nodes: BinaryHeap::new(),
+            query_point,
+            factor: base.clone() * base,
+        };
+        result.extend_heap(&root.children);
+        result
+    }
+
+    fn extend_heap(&mut self, children: &'a [RTreeNode<T>]) {
+        for child in children {
+            let distance = match child {
+                RTreeNode::DirectoryNode(ref data) => {
+                    data.mbr().min_dist2(&self.query_point) * self.factor.clone()
+                }
+                RTreeNode::Leaf(ref t) => t.distance2(&self.query_point),
+            };
+            self.nodes.push(RTreeNodeDistanceWrapper {
+                node: child,
+                distance,
+            });
+        }
+    }
+}
+
+impl<'a, T> Iterator for ApproximateNearestNeighborIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    type Item = (&'a T, <T::Point as PointN>::Scalar);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(current) = self.nodes.pop() {
+            match current {
+                RTreeNodeDistanceWrapper {
+                    node: RTreeNode::DirectoryNode(ref data),
+                    ..
+                } => {
+                    self.extend_heap(&data.children);
+                }
+                RTreeNodeDistanceWrapper {
+                    node: RTreeNode::Leaf(ref t),
+                    distance,
+                } => {
+                    return Some((t, distance));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject,
+{
+    /// Returns an object whose distance to a point is within `1 + epsilon` of the
+    /// nearest neighbor's distance.
+    ///
+    /// The returned tuple contains the object's actual squared distance. Subtrees whose
+    /// minimal squared distance times `(1 + epsilon)²` exceeds the closest distance found
+    /// so far are not visited. An `epsilon` of zero yields the exact
+    /// nearest neighbor. Returns `None` if the tree is empty.
+    ///
+    /// # Panics
+    /// Panics if `epsilon` is negative.
+    pub fn approximate_nearest_neighbor(
+        &self,
+        query_point: &T::Point,
+        epsilon: <T::Point as PointN>::Scalar,
+    ) -> Option<(&T, <T::Point as PointN>::Scalar)> {
+        self.approximate_nearest_neighbor_iterator(query_point, epsilon)
+            .next()
+    }
+
+    /// Returns an iterator over the approximate nearest neighbors of a point.
+    ///
+    /// See `ApproximateNearestNeighborIterator` for the error bound. Taking the first `k`
+    /// items yields an approximate k-nearest neighbor query.
+    ///
+    /// # Panics
+    /// Panics if `epsilon` is negative.
+    pub fn approximate_nearest_neighbor_iterator(
+        &self,
+        query_point: &T::Point,
+        epsilon: <T::Point as PointN>::Scalar,
+    ) -> ApproximateNearestNeighborIterator<'_, T> {
+        ApproximateNearestNeighborIterator::new(&self.root, query_point.clone(), epsilon)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rtree::RTree;
+    use crate::traits::SpatialObject;
+    use rand::distributions::{Distribution, Standard};
+    use rand::{SeedableRng, XorShiftRng};
+
+    #[test]
+    fn test_approximate_nearest_neighbor() {
+        const EPSILON: f32 = 0.5;
+        let mut rng = XorShiftRng::from_seed(*b"Close is enough.");
+        let points: Vec<[f32; 4]> = (0..2000).map(|_| Standard.sample(&mut rng)).collect();
+        let tree = RTree::bulk_load(points.clone());
+        for _ in 0..100 {
+            let query: [f32; 4] = Standard.sample(&mut rng);
+            let (_, exact) = tree
+                .nearest_neighbor_iter_with_distance2(&query)
+                .next()
+                .unwrap();
+            let (found, distance2) = tree.approximate_nearest_neighbor(&query, EPSILON).unwrap();
+            assert_eq!(found.distance2(&query), distance2);
+            assert!(distance2 <= exact * (1.0 + EPSILON) * (1.0 + EPSILON));
+            assert_eq!(
+                tree.approximate_nearest_neighbor(&query, 0.0).unwrap().1,
+                exact
+            );
+        }
+        let empty: RTree<[f32; 4]> = RTree::new();
+        assert!(empty.approximate_nearest_neighbor(&[0.0; 4], 0.1).is_none());
+    }
+
+    #[test]
+    fn test_approximate_nearest_neighbor_iterator() {
+        const EPSILON: f32 = 0.2;
+        let mut rng = XorShiftRng::from_seed(*b"..near enough...");
+        let points: Vec<[f32; 4]> = (0..1000).map(|_| Standard.sample(&mut rng)).collect();
+        let tree = RTree::bulk_load(points.clone());
+        let query: [f32; 4] = Standard.sample(&mut rng);
+        let exact: Vec<_> = tree
+            .nearest_neighbor_iter_with_distance2(&query)
+            .map(|(_, distance2)| distance2)
+            .collect();
+        let approximate: Vec<_> = tree
+            .approximate_nearest_neighbor_iterator(&query, EPSILON)
+            .map(|(_, distance2)| distance2)
+            .collect();
+        assert_eq!(approximate.len(), points.len());
+        for (approximate, exact) in approximate.iter().zip(&exact) {
+            assert!(*approximate <= exact * (1.0 + EPSILON) * (1.0 + EPSILON));
+        }
+    }
+}
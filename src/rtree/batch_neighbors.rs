@@ -0,0 +1,249 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Nearest neighbor queries for all objects of a tree, batched per leaf node.
+
+use super::{DirectoryNodeData, RTree, RTreeNode, RTreeNodeDistanceWrapper};
+use crate::boundingrect::BoundingRect;
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+use std::collections::BinaryHeap;
+
+impl<T> DirectoryNodeData<T>
+where
+    T: SpatialObject,
+{
+    /// Collects all nodes of depth 1, whose children are all leaves.
+    fn collect_leaf_nodes<'a>(&'a self, result: &mut Vec<&'a DirectoryNodeData<T>>) {
+        if self.depth == 1 {
+            if !self.children.is_empty() {
+                result.push(self);
+            }
+            return;
+        }
+        for child in &self.children {
+            if let RTreeNode::DirectoryNode(ref data) = child {
+                data.collect_leaf_nodes(result);
+            }
+        }
+    }
+
+    /// Visits all nodes of depth 1 ordered by the minimal distance of their bounding
+    /// rectangle to `mbr`, until `visit` returns `false`.
+    fn visit_leaf_nodes_by_distance<'a, F>(&'a self, mbr: &BoundingRect<T::Point>, mut visit: F)
+    where
+        F: FnMut(&'a [RTreeNode<T>], <T::Point as PointN>::Scalar) -> bool,
+    {
+        if self.depth == 1 {
+            visit(&self.children, mbr.min_dist2_rect(&self.mbr()));
+            return;
+        }
+        let mut nodes = BinaryHeap::new();
+        let wrap = |child: &'a RTreeNode<T>| RTreeNodeDistanceWrapper {
+            node: child,
+            distance: mbr.min_dist2_rect(&child.mbr()),
+        };
+        nodes.extend(self.children.iter().map(wrap));
+        while let Some(RTreeNodeDistanceWrapper { node, distance }) = nodes.pop() {
+            if let RTreeNode::DirectoryNode(ref data) = node {
+                if data.depth > 1 {
+                    nodes.extend(data.children.iter().map(wrap));
+                } else if !visit(&data.children, distance) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject,
+{
+    /// Returns the nearest neighbor of every object in the tree.
+    ///
+    /// Each item is a tuple `(object, neighbor, distance2)`, an object is never its own
+    /// neighbor. As for `closest_pair_iterator`, distances between objects are measured
+    /// between their minimal bounding rectangles, which is exact for points.
+    ///
+    /// Objects sharing a leaf node are queried together in a single traversal of the tree,
+    /// which is considerably faster than calling `nearest_neighbor` for each object.
+    pub fn all_nearest_neighbors(&self) -> Vec<(&T, &T, <T::Point as PointN>::Scalar)> {
+        let mut leaf_nodes = Vec::new();
+        self.root.collect_leaf_nodes(&mut leaf_nodes);
+        let mut result = Vec::with_capacity(self.size);
+        for leaf_node in leaf_nodes {
+            let queries: Vec<_> = leaf_node
+                .children
+                .iter()
+                .filter_map(|child| match child {
+                    RTreeNode::Leaf(ref t) => Some((t, t.mbr())),
+                    _ => None,
+                })
+                .collect();
+            let mut nearest: Vec<_> = queries.iter().map(|_| None).collect();
+            self.root
+                .visit_leaf_nodes_by_distance(&leaf_node.mbr(), |children, distance| {
+                    // Stop if no query's neighbor can be improved anymore
+                    let done = nearest.iter().all(|best| match best {
+                        Some((_, best)) => *best <= distance,
+                        None => false,
+                    });
+                    if done {
+                        return false;
+                    }
+                    for child in children {
+                        if let RTreeNode::Leaf(ref object) = child {
+                            let mbr = object.mbr();
+                            for ((query, query_mbr), best) in queries.iter().zip(&mut nearest) {
+                                if ::std::ptr::eq(*query, object) {
+                                    continue;
+                                }
+                                let distance = query_mbr.min_dist2_rect(&mbr);
+                                match best {
+                                    Some((_, ref best)) if *best <= distance => {}
+                                    _ => *best = Some((object, distance)),
+                                }
+                            }
+                        }
+                    }
+                    true
+                });
+            result.extend(
+                queries
+                    .into_iter()
+                    .zip(nearest)
+                    .filter_map(|((query, _), best)| best.map(|(t, d)| (query, t, d))),
+            );
+        }
+        result
+    }
+
+    /// Returns all objects that have `query_point` among their `k` nearest neighbors.
+    ///
+    /// An object is returned if fewer than `k` other objects in the tree are strictly
+    /// closer to it than `query_point`. Distances between objects are measured between
+    /// their minimal bounding rectangles, see `all_nearest_neighbors`.
+    ///
+    /// Objects sharing a leaf node are checked together in a single traversal of the tree.
+    /// The traversal of a leaf node ends as soon as each of its objects has either been
+    /// ruled out or no closer object can be found anymore.
+    pub fn reverse_nearest_neighbors(&self, query_point: &T::Point, k: usize) -> Vec<&T> {
+        let mut result = Vec::new();
+        if k == 0 {
+            return result;
+        }
+        let mut leaf_nodes = Vec::new();
+        self.root.collect_leaf_nodes(&mut leaf_nodes);
+        for leaf_node in leaf_nodes {
+            // Each candidate stores its distance to the query point and the number of
+            // objects found that are closer
+            let mut candidates: Vec<_> = leaf_node
+                .children
+                .iter()
+                .filter_map(|child| match child {
+                    RTreeNode::Leaf(ref t) => Some((t, t.mbr(), t.distance2(query_point), 0)),
+                    _ => None,
+                })
+                .collect();
+            self.root
+                .visit_leaf_nodes_by_distance(&leaf_node.mbr(), |children, distance| {
+                    candidates.retain(|&(_, _, _, closer)| closer < k);
+                    if candidates
+                        .iter()
+                        .all(|(_, _, query_distance, _)| *query_distance <= distance)
+                    {
+                        return false;
+                    }
+                    for child in children {
+                        if let RTreeNode::Leaf(ref object) = child {
+                            let mbr = object.mbr();
+                            for (candidate, candidate_mbr, query_distance, closer) in
+                                &mut candidates
+                            {
+                                if !::std::ptr::eq(*candidate, object)
+                                    && candidate_mbr.min_dist2_rect(&mbr) < *query_distance
+                                {
+                                    *closer += 1;
+                                }
+                            }
+                        }
+                    }
+                    true
+                });
+            result.extend(
+                candidates
+                    .into_iter()
+                    .filter(|&(_, _, _, closer)| closer < k)
+                    .map(|(candidate, _, _, _)| candidate),
+            );
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rtree::RTree;
+    use crate::testutils::*;
+    use crate::traits::SpatialObject;
+    use cgmath::Point2;
+
+    #[test]
+    fn test_all_nearest_neighbors() {
+        let (tree, points) = create_random_tree::<f64>(500, b"Everybody needs ");
+        let result = tree.all_nearest_neighbors();
+        assert_eq!(result.len(), points.len());
+        for (point, neighbor, distance2) in result {
+            let expected = points
+                .iter()
+                .filter(|p| *p != point)
+                .map(|p| p.distance2(point))
+                .fold(f64::INFINITY, f64::min);
+            assert_eq!(distance2, expected);
+            assert_eq!(neighbor.distance2(point), expected);
+        }
+
+        let mut single = RTree::new();
+        single.insert(Point2::new(1.0, 2.0));
+        assert!(single.all_nearest_neighbors().is_empty());
+        single.insert(Point2::new(1.0, 2.0));
+        assert_eq!(single.all_nearest_neighbors().len(), 2);
+    }
+
+    #[test]
+    fn test_reverse_nearest_neighbors() {
+        let (tree, points) = create_random_tree::<f64>(400, b"..a good neighbo");
+        let queries = random_points_with_seed::<f64>(10, b"r, who's nearby?");
+        for query in &queries {
+            for k in 1..4 {
+                let mut result = tree.reverse_nearest_neighbors(query, k);
+                let mut expected: Vec<_> = points
+                    .iter()
+                    .filter(|p| {
+                        let query_distance = p.distance2(query);
+                        let closer = points
+                            .iter()
+                            .filter(|o| o != p && o.distance2(p) < query_distance)
+                            .count();
+                        closer < k
+                    })
+                    .collect();
+                let compare = |l: &&Point2<f64>, r: &&Point2<f64>| {
+                    (l.x, l.y).partial_cmp(&(r.x, r.y)).unwrap()
+                };
+                result.sort_by(compare);
+                expected.sort_by(compare);
+                assert_eq!(result, expected);
+            }
+            assert!(tree.reverse_nearest_neighbors(query, 0).is_empty());
+        }
+        let empty: RTree<Point2<f64>> = RTree::new();
+        assert!(empty.reverse_nearest_neighbors(&queries[0], 1).is_empty());
+    }
+}
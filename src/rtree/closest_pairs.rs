@@ -0,0 +1,213 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{DirectoryNodeData, DistanceWrapper, RTreeNode};
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+use std::collections::BinaryHeap;
+
+/// An iterator over pairs of objects from two r-trees, ordered by their distance.
+///
+/// This `struct` is created by the `closest_pair_iterator` and
+/// `self_closest_pair_iterator` methods on `RTree`. Pairs of subtrees are kept in a
+/// priority queue ordered by the minimal distance of their bounding rectangles, so
+/// only pairs that are at least as close as the next result are ever expanded.
+///
+/// Each item is a tuple `(a, b, distance2)`. The distance of two objects is the
+/// squared minimal distance of their bounding rectangles, which is exact for points.
+pub struct ClosestPairIterator<'a, T, U>
+where
+    T: SpatialObject + 'a,
+    U: SpatialObject<Point = T::Point> + 'a,
+{
+    pairs: BinaryHeap<NodePairDistanceWrapper<'a, T, U>>,
+}
+
+type NodePairDistanceWrapper<'a, T, U> =
+    DistanceWrapper<NodePair<'a, T, U>, <<T as SpatialObject>::Point as PointN>::Scalar>;
+
+struct NodePair<'a, T, U>
+where
+    T: SpatialObject + 'a,
+    U: SpatialObject<Point = T::Point> + 'a,
+{
+    first: &'a RTreeNode<T>,
+    second: &'a RTreeNode<U>,
+    // Set if both nodes are the same node of a self join
+    same: bool,
+}
+
+impl<'a, T, U> ClosestPairIterator<'a, T, U>
+where
+    T: SpatialObject + 'a,
+    U: SpatialObject<Point = T::Point> + 'a,
+{
+    pub(super) fn new(first: &'a DirectoryNodeData<T>, second: &'a DirectoryNodeData<U>) -> Self {
+        let mut result = ClosestPairIterator {
+            pairs: BinaryHeap::new(),
+        };
+        result.push_pairs(&first.children, &second.children, false);
+        result
+    }
+
+    fn push_pairs(&mut self, first: &'a [RTreeNode<T>], second: &'a [RTreeNode<U>], same: bool) {
+        for (index, first_child) in first.iter().enumerate() {
+            let first_mbr = first_child.mbr();
+            // Within the same node, only pairs (i, j) with i <= j are considered
+            let skip = if same { index } else { 0 };
+            for (offset, second_child) in second.iter().enumerate().skip(skip) {
+                let same = same && offset == index;
+                if same && first_child.depth() == 0 {
+                    // An object is not paired with itself
+                    continue;
+                }
+                self.pairs.push(DistanceWrapper {
+                    node: NodePair {
+                        first: first_child,
+                        second: second_child,
+                        same,
+                    },
+                    distance: first_mbr.min_dist2_rect(&second_child.mbr()),
+                });
+            }
+        }
+    }
+}
+
+impl<'a, T> ClosestPairIterator<'a, T, T>
+where
+    T: SpatialObject + 'a,
+{
+    pub(super) fn new_self_join(root: &'a DirectoryNodeData<T>) -> Self {
+        let mut result = ClosestPairIterator {
+            pairs: BinaryHeap::new(),
+        };
+        result.push_pairs(&root.children, &root.children, true);
+        result
+    }
+}
+
+impl<'a, T, U> Iterator for ClosestPairIterator<'a, T, U>
+where
+    T: SpatialObject + 'a,
+    U: SpatialObject<Point = T::Point> + 'a,
+{
+    type Item = (&'a T, &'a U, <T::Point as PointN>::Scalar);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(current) = self.pairs.pop() {
+            let DistanceWrapper {
+                node:
+                    NodePair {
+                        first,
+                        second,
+                        same,
+                    },
+                distance,
+            } = current;
+            match (first, second) {
+                (RTreeNode::Leaf(ref first), RTreeNode::Leaf(ref second)) => {
+                    return Some((first, second, distance));
+                }
+                (RTreeNode::DirectoryNode(ref first), RTreeNode::DirectoryNode(ref second))
+                    if first.depth == second.depth =>
+                {
+                    self.push_pairs(&first.children, &second.children, same);
+                }
+                (RTreeNode::DirectoryNode(ref data), second) if data.depth > second.depth() => {
+                    self.push_pairs(&data.children, ::std::slice::from_ref(second), false);
+                }
+                (first, RTreeNode::DirectoryNode(ref data)) => {
+                    self.push_pairs(::std::slice::from_ref(first), &data.children, false);
+                }
+                (RTreeNode::DirectoryNode(_), RTreeNode::Leaf(_)) => unreachable!(),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rtree::RTree;
+    use crate::testutils::*;
+    use crate::traits::SpatialObject;
+    use cgmath::Point2;
+
+    #[test]
+    fn test_closest_pairs() {
+        let (sensors, sensor_points) = create_random_tree::<f64>(200, b"Sensors all arou");
+        let facilities = RTree::bulk_load(random_points_with_seed::<f64>(700, b"nd the facilitie"));
+
+        let mut expected = Vec::new();
+        for p in &sensor_points {
+            for q in facilities.iter() {
+                expected.push(p.distance2(q));
+            }
+        }
+        expected.sort_by(|l, r| l.partial_cmp(r).unwrap());
+        let result = sensors.closest_pairs(&facilities, 500);
+        assert_eq!(result.len(), 500);
+        for ((p, q, distance2), expected) in result.iter().zip(&expected) {
+            assert_eq!(p.distance2(q), *distance2);
+            assert_eq!(distance2, expected);
+        }
+        assert_eq!(
+            sensors.closest_pair_iterator(&facilities).count(),
+            expected.len()
+        );
+        let (p, q, distance2) = facilities.closest_pair(&sensors).unwrap();
+        assert_eq!(distance2, expected[0]);
+        assert_eq!(q.distance2(p), expected[0]);
+    }
+
+    #[test]
+    fn test_self_closest_pairs() {
+        let (tree, points) = create_random_tree::<f64>(300, b"Birds of a feath");
+        let mut expected = Vec::new();
+        for (index, p) in points.iter().enumerate() {
+            for q in &points[index + 1..] {
+                expected.push(p.distance2(q));
+            }
+        }
+        expected.sort_by(|l, r| l.partial_cmp(r).unwrap());
+        let all: Vec<_> = tree.self_closest_pair_iterator().collect();
+        assert_eq!(all.len(), expected.len());
+        for ((p, q, distance2), expected) in all.iter().zip(&expected) {
+            assert_ne!(p, q);
+            assert_eq!(distance2, expected);
+        }
+        let closest = tree.self_closest_pairs(10);
+        assert_eq!(closest.len(), 10);
+        assert_eq!(
+            tree.self_closest_pair().map(|(_, _, d)| d),
+            Some(expected[0])
+        );
+    }
+
+    #[test]
+    fn test_closest_pairs_small_trees() {
+        let empty: RTree<Point2<f64>> = RTree::new();
+        let (tree, _) = create_random_tree::<f64>(100, b"Only one of us..");
+        assert!(empty.closest_pair(&tree).is_none());
+        assert!(tree.closest_pair(&empty).is_none());
+        assert!(empty.self_closest_pair().is_none());
+
+        let mut single = RTree::new();
+        single.insert(Point2::new(0.5, 0.5));
+        assert!(single.self_closest_pair().is_none());
+        // Trees of different height
+        assert_eq!(single.closest_pair_iterator(&tree).count(), 100);
+        assert_eq!(tree.closest_pair_iterator(&single).count(), 100);
+        single.insert(Point2::new(0.5, 0.5));
+        assert_eq!(
+            single.self_closest_pair(),
+            Some((&Point2::new(0.5, 0.5), &Point2::new(0.5, 0.5), 0.0))
+        );
+    }
+}
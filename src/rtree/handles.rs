@@ -0,0 +1,477 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{
+    DirectoryNodeData, NodeId, NodeObserver, RTree, RTreeIterator, RTreeNode, RTreeObjectMut,
+    RTreeOptions,
+};
+use crate::boundingrect::BoundingRect;
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+use smallvec::SmallVec;
+use std::collections::HashMap;
+use std::ops::Deref;
+
+#[cfg(feature = "serde_serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Identifies an object inserted into a `HandleRTree`.
+///
+/// Handles stay valid until their object is removed, independent of any other
+/// modification of the tree. Handles are never reused by the same tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+pub struct RTreeHandle(u64);
+
+/// An object stored in a `HandleRTree`, together with its handle.
+///
+/// Dereferences to the object itself.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+pub struct HandleEntry<T> {
+    handle: RTreeHandle,
+    object: T,
+}
+
+impl<T> HandleEntry<T> {
+    /// Returns the object's handle.
+    pub fn handle(&self) -> RTreeHandle {
+        self.handle
+    }
+
+    /// Returns the object.
+    pub fn object(&self) -> &T {
+        &self.object
+    }
+}
+
+impl<T> Deref for HandleEntry<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.object
+    }
+}
+
+impl<T> SpatialObject for HandleEntry<T>
+where
+    T: SpatialObject,
+{
+    type Point = T::Point;
+
+    fn mbr(&self) -> BoundingRect<T::Point> {
+        self.object.mbr()
+    }
+
+    fn distance2(&self, point: &T::Point) -> <T::Point as PointN>::Scalar {
+        self.object.distance2(point)
+    }
+
+    fn contains(&self, point: &T::Point) -> bool {
+        self.object.contains(point)
+    }
+}
+
+// The ids of the directory nodes from a child of the root down to a leaf node
+type NodePath = SmallVec<[NodeId; 8]>;
+
+/// Maps each handle to the leaf node containing its object and each directory node
+/// to its parent.
+#[derive(Default)]
+struct HandleIndex {
+    leaf_nodes: HashMap<RTreeHandle, NodeId>,
+    parents: HashMap<NodeId, NodeId>,
+}
+
+impl<T> NodeObserver<HandleEntry<T>> for HandleIndex
+where
+    T: SpatialObject,
+{
+    fn added(&mut self, child: &RTreeNode<HandleEntry<T>>, parent: NodeId) {
+        match child {
+            RTreeNode::Leaf(ref entry) => self.leaf_nodes.insert(entry.handle, parent),
+            RTreeNode::DirectoryNode(ref data) => self.parents.insert(data.id, parent),
+        };
+    }
+
+    fn dropped(&mut self, node: NodeId) {
+        self.parents.remove(&node);
+    }
+}
+
+/// An r-tree that identifies its objects by handles.
+///
+/// Every inserted object is assigned an `RTreeHandle` that can be used to access, modify
+/// or remove the object later on. Unlike `RTree::remove`, this neither requires
+/// `T: PartialEq` nor searches by geometry: The tree keeps an index from each handle to
+/// the leaf node containing its object and from each node to its parent. The index is
+/// updated whenever entries are moved by splits, reinsertions or removals. Accessing a
+/// handle follows the path from the root to its leaf node and takes `O(log(n))` time,
+/// even if many objects share the same position.
+///
+/// All other queries are available through `tree()`, which yields the contained objects
+/// wrapped in a `HandleEntry`.
+///
+/// # Example
+/// ```
+/// extern crate cgmath;
+/// extern crate spade;
+///
+/// use cgmath::Point2;
+/// use spade::rtree::HandleRTree;
+///
+/// fn main() {
+///     let mut tree = HandleRTree::new();
+///     let first = tree.insert(Point2::new(0.0, 1.0));
+///     let second = tree.insert(Point2::new(0.0, 1.0));
+///     tree.update_position(first, Point2::new(2.0, 2.0));
+///     assert_eq!(tree.get(first), Some(&Point2::new(2.0, 2.0)));
+///     assert_eq!(tree.remove_by_handle(second), Some(Point2::new(0.0, 1.0)));
+///     assert_eq!(tree.get(second), None);
+///     assert_eq!(tree.tree().nearest_neighbor(&Point2::new(0.0, 0.0)).unwrap().handle(), first);
+/// }
+/// ```
+pub struct HandleRTree<T>
+where
+    T: SpatialObject,
+{
+    tree: RTree<HandleEntry<T>>,
+    index: HandleIndex,
+    next_handle: u64,
+}
+
+impl<T> Default for HandleRTree<T>
+where
+    T: SpatialObject,
+{
+    fn default() -> Self {
+        HandleRTree::new()
+    }
+}
+
+impl<T> HandleRTree<T>
+where
+    T: SpatialObject,
+{
+    /// Creates an empty tree.
+    pub fn new() -> Self {
+        HandleRTree::new_with_options(Default::default())
+    }
+
+    /// Creates an empty tree with custom options.
+    pub fn new_with_options(options: RTreeOptions) -> Self {
+        HandleRTree {
+            tree: RTree::new_with_options(options),
+            index: HandleIndex::default(),
+            next_handle: 0,
+        }
+    }
+
+    /// Returns the underlying tree, e.g. to run queries.
+    pub fn tree(&self) -> &RTree<HandleEntry<T>> {
+        &self.tree
+    }
+
+    /// Returns the number of contained objects.
+    pub fn size(&self) -> usize {
+        self.tree.size()
+    }
+
+    /// Returns an iterator over all contained objects and their handles.
+    pub fn iter(&self) -> RTreeIterator<'_, HandleEntry<T>> {
+        self.tree.iter()
+    }
+
+    /// Returns `true` if the handle's object is contained in this tree.
+    pub fn contains_handle(&self, handle: RTreeHandle) -> bool {
+        self.index.leaf_nodes.contains_key(&handle)
+    }
+
+    /// Inserts a new object and returns its handle.
+    pub fn insert(&mut self, object: T) -> RTreeHandle {
+        let handle = RTreeHandle(self.next_handle);
+        self.next_handle += 1;
+        self.insert_with_handle(handle, object);
+        handle
+    }
+
+    fn insert_with_handle(&mut self, handle: RTreeHandle, object: T) {
+        let entry = RTreeNode::Leaf(HandleEntry { handle, object });
+        self.tree.insert_observed_node(entry, &mut self.index);
+        self.tree.size += 1;
+    }
+
+    /// Returns the path from the root to the leaf node containing a handle's object.
+    fn path(&self, handle: RTreeHandle) -> Option<NodePath> {
+        let mut node = *self.index.leaf_nodes.get(&handle)?;
+        let mut path = NodePath::new();
+        while node != self.tree.root.id {
+            path.push(node);
+            node = self.index.parents[&node];
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn leaf_node(&self, handle: RTreeHandle) -> Option<&DirectoryNodeData<HandleEntry<T>>> {
+        let mut node = &self.tree.root;
+        for id in self.path(handle)? {
+            node = node.child_node(id).expect("Handle index is out of date");
+        }
+        Some(node)
+    }
+
+    /// Returns the object of a handle.
+    ///
+    /// Returns `None` if the object has already been removed.
+    pub fn get(&self, handle: RTreeHandle) -> Option<&T> {
+        self.leaf_node(handle)?
+            .children
+            .iter()
+            .find_map(|child| match child {
+                RTreeNode::Leaf(ref entry) if entry.handle == handle => Some(&entry.object),
+                _ => None,
+            })
+    }
+
+    /// Returns a mutable reference to the object of a handle.
+    ///
+    /// *Do not change the object's minimal bounding box*, see `RTreeObjectMut`.
+    /// Use `update_position` to move an object.
+    pub fn get_mut(&mut self, handle: RTreeHandle) -> Option<RTreeObjectMut<'_, T>> {
+        let path = self.path(handle)?;
+        let mut node = &mut self.tree.root;
+        for id in path {
+            node = node
+                .child_node_mut(id)
+                .expect("Handle index is out of date");
+        }
+        node.children.iter_mut().find_map(|child| match child {
+            RTreeNode::Leaf(ref mut entry) if entry.handle == handle => {
+                Some(RTreeObjectMut::new(&mut entry.object))
+            }
+            _ => None,
+        })
+    }
+
+    /// Removes the object of a handle and returns it.
+    ///
+    /// The handle becomes invalid. Returns `None` if the object has already been removed.
+    pub fn remove_by_handle(&mut self, handle: RTreeHandle) -> Option<T> {
+        let path = self.path(handle)?;
+        let entry = self.tree.remove_at_path(
+            &path,
+            |entry: &HandleEntry<T>| entry.handle == handle,
+            &mut self.index,
+        )?;
+        self.index.leaf_nodes.remove(&handle);
+        // The root may have been replaced by one of its descendants
+        self.index.parents.remove(&self.tree.root.id);
+        Some(entry.object)
+    }
+
+    /// Replaces the object of a handle, e.g. to move it to a new position.
+    ///
+    /// The handle stays valid and refers to the new object afterwards. Returns the
+    /// replaced object, or `None` if the handle's object has already been removed. In
+    /// this case, `new_object` is not inserted.
+    pub fn update_position(&mut self, handle: RTreeHandle, new_object: T) -> Option<T> {
+        let old_object = self.remove_by_handle(handle)?;
+        self.insert_with_handle(handle, new_object);
+        Some(old_object)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HandleEntry, HandleIndex, HandleRTree};
+    use crate::rtree::{DirectoryNodeData, RTreeNode};
+    use crate::testutils::*;
+    use crate::traits::SpatialObject;
+    use cgmath::Point2;
+
+    /// Checks that the index matches the tree's structure and contains no stale entries.
+    fn check_index<T: SpatialObject>(tree: &HandleRTree<T>) {
+        fn check_node<T: SpatialObject>(
+            node: &DirectoryNodeData<HandleEntry<T>>,
+            index: &HandleIndex,
+            num_nodes: &mut usize,
+        ) {
+            for child in &node.children {
+                match child {
+                    RTreeNode::Leaf(ref entry) => {
+                        assert_eq!(index.leaf_nodes[&entry.handle], node.id);
+                    }
+                    RTreeNode::DirectoryNode(ref data) => {
+                        assert_eq!(index.parents[&data.id], node.id);
+                        *num_nodes += 1;
+                        check_node(data, index, num_nodes);
+                    }
+                }
+            }
+        }
+        let mut num_nodes = 0;
+        check_node(&tree.tree.root, &tree.index, &mut num_nodes);
+        assert_eq!(tree.index.leaf_nodes.len(), tree.size());
+        assert_eq!(tree.index.parents.len(), num_nodes);
+    }
+
+    #[test]
+    fn test_handles() {
+        let points = random_points_with_seed::<f32>(1000, b"Get a handle on ");
+        let mut tree = HandleRTree::new();
+        let handles: Vec<_> = points
+            .iter()
+            .enumerate()
+            .map(|(index, point)| tree.insert(PointWithData::new(*point, index as u32)))
+            .collect();
+        assert_eq!(tree.size(), points.len());
+        for (index, handle) in handles.iter().enumerate() {
+            assert_eq!(tree.get(*handle).unwrap().data, index as u32);
+        }
+        for (index, handle) in handles.iter().enumerate().step_by(2) {
+            let removed = tree.remove_by_handle(*handle).unwrap();
+            assert_eq!(removed.data, index as u32);
+            assert!(tree.get(*handle).is_none());
+            assert!(!tree.contains_handle(*handle));
+            assert!(tree.remove_by_handle(*handle).is_none());
+        }
+        assert_eq!(tree.size(), points.len() / 2);
+        for (index, handle) in handles.iter().enumerate().skip(1).step_by(2) {
+            tree.get_mut(*handle).unwrap().data += 1000;
+            assert_eq!(tree.get(*handle).unwrap().data, index as u32 + 1000);
+        }
+    }
+
+    #[test]
+    fn test_handles_shared_position() {
+        let mut tree = HandleRTree::new();
+        let position = Point2::new(0.5, 0.5);
+        let handles: Vec<_> = (0..100)
+            .map(|index| tree.insert(PointWithData::new(position, index)))
+            .collect();
+        for (index, handle) in handles.iter().enumerate() {
+            assert_eq!(tree.get(*handle).unwrap().data, index as u32);
+        }
+        for (index, handle) in handles.iter().enumerate().rev() {
+            assert_eq!(tree.remove_by_handle(*handle).unwrap().data, index as u32);
+            assert_eq!(tree.size(), index);
+        }
+    }
+
+    #[test]
+    fn test_handles_many_shared_positions() {
+        let points = random_points_with_seed::<f32>(500, b"Crowded in here.");
+        let targets = random_points_with_seed::<f32>(500, b"Spread out again");
+        let position = Point2::new(0.25, -0.5);
+        let mut tree = HandleRTree::new();
+        let mut shared = Vec::new();
+        let mut others = Vec::new();
+        // Interleave both kinds of objects to cause splits and reinsertions
+        for (index, point) in points.iter().enumerate() {
+            let index = index as u32;
+            shared.push(tree.insert(PointWithData::new(position, index)));
+            others.push(tree.insert(PointWithData::new(*point, index)));
+        }
+        assert_eq!(tree.size(), 1000);
+        check_index(&tree);
+        for (index, handle) in shared.iter().enumerate() {
+            assert_eq!(tree.get(*handle).unwrap().data, index as u32);
+            tree.get_mut(*handle).unwrap().data += 1000;
+        }
+        for (index, (handle, target)) in shared.iter().zip(&targets).enumerate().step_by(2) {
+            let old = tree
+                .update_position(*handle, PointWithData::new(*target, index as u32))
+                .unwrap();
+            assert_eq!(old.data, index as u32 + 1000);
+            assert_eq!(tree.get(*handle).unwrap().position, *target);
+        }
+        check_index(&tree);
+        for (index, handle) in shared.iter().enumerate().skip(1).step_by(2) {
+            let removed = tree.remove_by_handle(*handle).unwrap();
+            assert_eq!(removed.position, position);
+            assert_eq!(removed.data, index as u32 + 1000);
+        }
+        assert_eq!(tree.size(), 750);
+        check_index(&tree);
+        for (index, handle) in others.iter().enumerate() {
+            assert_eq!(tree.get(*handle).unwrap().position, points[index]);
+        }
+        let at_position = tree
+            .tree()
+            .iter()
+            .filter(|entry| entry.position == position)
+            .count();
+        assert_eq!(at_position, 0);
+
+        for handle in shared.iter().chain(&others) {
+            assert!(tree.remove_by_handle(*handle).is_some() || !tree.contains_handle(*handle));
+            if tree.size() % 50 == 0 {
+                check_index(&tree);
+            }
+        }
+        assert_eq!(tree.size(), 0);
+        check_index(&tree);
+    }
+
+    #[test]
+    fn test_handle_lookup_path() {
+        const NUM_OBJECTS: usize = 5000;
+        let position = Point2::new(1.0f32, 1.0);
+        let mut tree = HandleRTree::new();
+        let handles: Vec<_> = (0..NUM_OBJECTS)
+            .map(|index| tree.insert(PointWithData::new(position, index as u32)))
+            .collect();
+        check_index(&tree);
+        // A search by position visits every node containing the position
+        assert!(tree.tree().statistics().average_node_accesses > 100.0);
+        let depth = tree.tree().root.depth;
+        for (index, handle) in handles.iter().enumerate() {
+            // A lookup visits only one node per level
+            assert_eq!(tree.path(*handle).unwrap().len(), depth - 1);
+            assert_eq!(tree.get(*handle).unwrap().data, index as u32);
+        }
+        for handle in handles.iter().step_by(3) {
+            assert!(tree.remove_by_handle(*handle).is_some());
+        }
+        check_index(&tree);
+        let depth = tree.tree().root.depth;
+        for (index, handle) in handles.iter().enumerate() {
+            match tree.get(*handle) {
+                Some(object) => {
+                    assert_eq!(object.data, index as u32);
+                    assert_eq!(tree.path(*handle).unwrap().len(), depth - 1);
+                }
+                None => assert_eq!(index % 3, 0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_position() {
+        let points = random_points_with_seed::<f32>(300, b"Move it, move it");
+        let targets = random_points_with_seed::<f32>(300, b"I like to move i");
+        let mut tree = HandleRTree::new();
+        let handles: Vec<_> = points.iter().map(|p| tree.insert(*p)).collect();
+        for (handle, target) in handles.iter().zip(targets.iter()) {
+            assert!(tree.update_position(*handle, *target).is_some());
+        }
+        assert_eq!(tree.size(), points.len());
+        for (handle, target) in handles.iter().zip(targets.iter()) {
+            assert_eq!(tree.get(*handle), Some(target));
+            let nearest = tree.tree().nearest_neighbor(target).unwrap();
+            assert_eq!(**nearest, *target);
+        }
+        let removed = handles[0];
+        tree.remove_by_handle(removed);
+        assert!(tree
+            .update_position(removed, Point2::new(0.0, 0.0))
+            .is_none());
+        assert_eq!(tree.size(), points.len() - 1);
+    }
+}
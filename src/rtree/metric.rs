@@ -0,0 +1,379 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::SelectionFunction;
+use crate::boundingrect::BoundingRect;
+use crate::misc::max_inline;
+use crate::point_traits::{PointN, PointNExtensions};
+use crate::traits::{HasPosition, SpatialObject};
+use num::{zero, Signed};
+
+/// A distance measure used by r-tree queries.
+///
+/// A metric defines both the distance between an object and a query point and a lower
+/// bound for the distance between a query point and any object within a rectangle.
+/// The r-tree uses the lower bound to prune subtrees, thus for correct results,
+/// `min_distance` must never be larger than `distance` for any object contained in the
+/// rectangle.
+///
+/// Distances only need to be comparable, not physically meaningful: `Euclidean` returns
+/// squared distances, which are cheaper to compute and sort in the same order.
+///
+/// # Example
+/// ```
+/// extern crate cgmath;
+/// extern crate spade;
+///
+/// use cgmath::Point2;
+/// use spade::rtree::{Manhattan, RTree};
+///
+/// fn main() {
+///     let mut tree = RTree::new();
+///     tree.insert(Point2::new(3.0, 3.0));
+///     tree.insert(Point2::new(0.0, 5.0));
+///     let query = Point2::new(0.0, 0.0);
+///     // (3, 3) is closer in euclidean space...
+///     assert_eq!(tree.nearest_neighbor(&query), Some(&Point2::new(3.0, 3.0)));
+///     // ...but not when moving along the grid
+///     assert_eq!(
+///         tree.nearest_neighbor_with_metric(&query, Manhattan),
+///         Some(&Point2::new(0.0, 5.0))
+///     );
+/// }
+/// ```
+pub trait Metric<T>
+where
+    T: SpatialObject,
+{
+    /// Returns the distance between an object and a point.
+    fn distance(&self, object: &T, point: &T::Point) -> <T::Point as PointN>::Scalar;
+
+    /// Returns a lower bound for the distance between a point and any object that is
+    /// contained in a rectangle.
+    fn min_distance(
+        &self,
+        rect: &BoundingRect<T::Point>,
+        point: &T::Point,
+    ) -> <T::Point as PointN>::Scalar;
+}
+
+/// The squared euclidean distance.
+///
+/// This is the default metric of all r-tree queries, it uses `SpatialObject::distance2`
+/// and `BoundingRect::min_dist2`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Euclidean;
+
+impl<T> Metric<T> for Euclidean
+where
+    T: SpatialObject,
+{
+    fn distance(&self, object: &T, point: &T::Point) -> <T::Point as PointN>::Scalar {
+        object.distance2(point)
+    }
+
+    fn min_distance(
+        &self,
+        rect: &BoundingRect<T::Point>,
+        point: &T::Point,
+    ) -> <T::Point as PointN>::Scalar {
+        rect.min_dist2(point)
+    }
+}
+
+/// The manhattan (taxicab) distance, the sum of all absolute coordinate differences.
+///
+/// Only supported for point like objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Manhattan;
+
+/// The chebyshev (chessboard) distance, the largest absolute coordinate difference.
+///
+/// Only supported for point like objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Chebyshev;
+
+/// A manhattan distance that weights each axis differently.
+///
+/// The distance is the sum of all absolute coordinate differences, each multiplied with
+/// its axis' weight. Weights must not be negative.
+/// Only supported for point like objects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeightedManhattan<V: PointN> {
+    weights: V,
+}
+
+impl<V: PointN> WeightedManhattan<V> {
+    /// Creates a new weighted metric. Each component of `weights` contains the
+    /// weight of the corresponding axis.
+    pub fn new(weights: V) -> Self {
+        WeightedManhattan { weights }
+    }
+
+    fn length(&self, delta: &V) -> V::Scalar {
+        delta
+            .component_wise(&self.weights, |value, weight| value.abs() * weight)
+            .fold(zero(), |acc, value| acc + value)
+    }
+}
+
+/// A squared euclidean distance that weights each axis differently.
+///
+/// The distance is the sum of all squared coordinate differences, each multiplied with
+/// its axis' weight. Weights must not be negative.
+/// Only supported for point like objects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeightedEuclidean<V: PointN> {
+    weights: V,
+}
+
+impl<V: PointN> WeightedEuclidean<V> {
+    /// Creates a new weighted metric. Each component of `weights` contains the
+    /// weight of the corresponding axis.
+    pub fn new(weights: V) -> Self {
+        WeightedEuclidean { weights }
+    }
+
+    fn length(&self, delta: &V) -> V::Scalar {
+        delta
+            .component_wise(&self.weights, |value, weight| {
+                value.clone() * value * weight
+            })
+            .fold(zero(), |acc, value| acc + value)
+    }
+}
+
+impl Manhattan {
+    fn length<V: PointN>(&self, delta: &V) -> V::Scalar {
+        delta.fold(zero(), |acc, value| acc + value.abs())
+    }
+}
+
+impl Chebyshev {
+    fn length<V: PointN>(&self, delta: &V) -> V::Scalar {
+        delta.fold(zero(), |acc, value| max_inline(acc, value.abs()))
+    }
+}
+
+impl<T> Metric<T> for Manhattan
+where
+    T: HasPosition,
+{
+    fn distance(&self, object: &T, point: &T::Point) -> <T::Point as PointN>::Scalar {
+        self.length(&object.position().sub(point))
+    }
+
+    fn min_distance(
+        &self,
+        rect: &BoundingRect<T::Point>,
+        point: &T::Point,
+    ) -> <T::Point as PointN>::Scalar {
+        self.length(&rect.min_point(point).sub(point))
+    }
+}
+
+impl<T> Metric<T> for Chebyshev
+where
+    T: HasPosition,
+{
+    fn distance(&self, object: &T, point: &T::Point) -> <T::Point as PointN>::Scalar {
+        self.length(&object.position().sub(point))
+    }
+
+    fn min_distance(
+        &self,
+        rect: &BoundingRect<T::Point>,
+        point: &T::Point,
+    ) -> <T::Point as PointN>::Scalar {
+        self.length(&rect.min_point(point).sub(point))
+    }
+}
+
+impl<T> Metric<T> for WeightedManhattan<T::Point>
+where
+    T: HasPosition,
+{
+    fn distance(&self, object: &T, point: &T::Point) -> <T::Point as PointN>::Scalar {
+        self.length(&object.position().sub(point))
+    }
+
+    fn min_distance(
+        &self,
+        rect: &BoundingRect<T::Point>,
+        point: &T::Point,
+    ) -> <T::Point as PointN>::Scalar {
+        self.length(&rect.min_point(point).sub(point))
+    }
+}
+
+impl<T> Metric<T> for WeightedEuclidean<T::Point>
+where
+    T: HasPosition,
+{
+    fn distance(&self, object: &T, point: &T::Point) -> <T::Point as PointN>::Scalar {
+        self.length(&object.position().sub(point))
+    }
+
+    fn min_distance(
+        &self,
+        rect: &BoundingRect<T::Point>,
+        point: &T::Point,
+    ) -> <T::Point as PointN>::Scalar {
+        self.length(&rect.min_point(point).sub(point))
+    }
+}
+
+/// Selects all objects within a given distance of a point, measured with a `Metric`.
+///
+/// Used by `RTree::lookup_in_circle_with_metric`.
+pub struct SelectWithinDistance<V: PointN, M> {
+    origin: V,
+    max_distance: V::Scalar,
+    metric: M,
+}
+
+impl<V: PointN, M> SelectWithinDistance<V, M> {
+    /// Creates a new selection function.
+    ///
+    /// `max_distance` is measured with `metric`, e.g. it is the squared distance for
+    /// `Euclidean`.
+    pub fn new(origin: V, max_distance: V::Scalar, metric: M) -> Self {
+        SelectWithinDistance {
+            origin,
+            max_distance,
+            metric,
+        }
+    }
+}
+
+impl<T, M> SelectionFunction<T> for SelectWithinDistance<T::Point, M>
+where
+    T: SpatialObject,
+    M: Metric<T>,
+{
+    fn should_descend(&self, mbr: &BoundingRect<T::Point>) -> bool {
+        self.metric.min_distance(mbr, &self.origin) <= self.max_distance
+    }
+
+    fn matches(&self, object: &T) -> bool {
+        self.metric.distance(object, &self.origin) < self.max_distance
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Chebyshev, Euclidean, Manhattan, Metric, WeightedEuclidean, WeightedManhattan};
+    use crate::boundingrect::BoundingRect;
+    use crate::rtree::RTree;
+    use crate::testutils::*;
+    use cgmath::Point2;
+
+    fn check_lower_bound<M: Metric<Point2<f32>>>(metric: &M) {
+        let points = random_points_with_seed::<f32>(100, b"Lower than you t");
+        let queries = random_points_with_seed::<f32>(20, b"hink, and lower!");
+        for chunk in points.chunks(10) {
+            let rect = BoundingRect::from_points(chunk.iter().cloned());
+            for query in &queries {
+                let bound = metric.min_distance(&rect, query);
+                for point in chunk {
+                    assert!(bound <= metric.distance(point, query));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_metric_distances() {
+        let p = Point2::new(1.0f32, 2.0);
+        let q = Point2::new(-2.0f32, 6.0);
+        assert_eq!(Euclidean.distance(&p, &q), 25.0);
+        assert_eq!(Manhattan.distance(&p, &q), 7.0);
+        assert_eq!(Chebyshev.distance(&p, &q), 4.0);
+        let weights = Point2::new(2.0, 0.5);
+        assert_eq!(WeightedManhattan::new(weights).distance(&p, &q), 8.0);
+        assert_eq!(WeightedEuclidean::new(weights).distance(&p, &q), 26.0);
+
+        let rect = BoundingRect::from_corners(&Point2::new(0.0f32, 0.0), &Point2::new(1.0, 1.0));
+        assert_eq!(
+            Metric::<Point2<f32>>::min_distance(&Manhattan, &rect, &Point2::new(0.5, 0.5)),
+            0.0
+        );
+        assert_eq!(
+            Metric::<Point2<f32>>::min_distance(&Manhattan, &rect, &Point2::new(3.0, -1.0)),
+            3.0
+        );
+        assert_eq!(
+            Metric::<Point2<f32>>::min_distance(&Chebyshev, &rect, &Point2::new(3.0, -1.0)),
+            2.0
+        );
+    }
+
+    #[test]
+    fn test_metric_lower_bounds() {
+        check_lower_bound(&Euclidean);
+        check_lower_bound(&Manhattan);
+        check_lower_bound(&Chebyshev);
+        check_lower_bound(&WeightedManhattan::new(Point2::new(3.0, 0.2)));
+        check_lower_bound(&WeightedEuclidean::new(Point2::new(0.1, 2.0)));
+    }
+
+    fn check_metric_queries<M: Metric<Point2<f32>> + Clone>(metric: M, max_distance: f32) {
+        let (tree, points) = create_random_tree::<f32>(300, b"Measure twice, c");
+        let queries = random_points_with_seed::<f32>(10, b"ut once. Or not.");
+        for query in &queries {
+            let mut sorted: Vec<_> = points
+                .iter()
+                .map(|p| (metric.distance(p, query), *p))
+                .collect();
+            sorted.sort_by(|l, r| l.0.partial_cmp(&r.0).unwrap());
+            let distances: Vec<_> = tree
+                .nearest_neighbor_iterator_with_metric(query, metric.clone())
+                .map(|p| metric.distance(p, query))
+                .collect();
+            let expected: Vec<_> = sorted.iter().map(|&(d, _)| d).collect();
+            assert_eq!(distances, expected);
+            let nearest = tree
+                .nearest_neighbor_with_metric(query, metric.clone())
+                .unwrap();
+            assert_eq!(metric.distance(nearest, query), sorted[0].0);
+
+            let in_circle = tree.lookup_in_circle_with_metric(query, &max_distance, metric.clone());
+            let expected = sorted.iter().filter(|&&(d, _)| d < max_distance).count();
+            assert_eq!(in_circle.len(), expected);
+            assert!(in_circle
+                .iter()
+                .all(|p| metric.distance(*p, query) < max_distance));
+        }
+    }
+
+    #[test]
+    fn test_metric_queries() {
+        check_metric_queries(Euclidean, 0.04);
+        check_metric_queries(Manhattan, 0.3);
+        check_metric_queries(Chebyshev, 0.2);
+        check_metric_queries(WeightedManhattan::new(Point2::new(1.0, 4.0)), 0.5);
+        check_metric_queries(WeightedEuclidean::new(Point2::new(0.5, 3.0)), 0.05);
+    }
+
+    #[test]
+    fn test_metric_queries_empty() {
+        let tree: RTree<Point2<f32>> = RTree::new();
+        let query = Point2::new(0.0, 0.0);
+        assert!(tree
+            .nearest_neighbor_with_metric(&query, Manhattan)
+            .is_none());
+        assert_eq!(
+            tree.nearest_neighbor_iterator_with_metric(&query, Chebyshev)
+                .count(),
+            0
+        );
+        assert!(tree
+            .lookup_in_circle_with_metric(&query, &1.0, Manhattan)
+            .is_empty());
+    }
+}
@@ -0,0 +1,3582 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Implementation of an n-dimensional r*-tree.
+
+mod aggregate;
+mod approximate;
+mod batch_neighbors;
+mod closest_pairs;
+mod handles;
+mod metric;
+mod node_ref;
+mod object_mut;
+mod packed;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod persistent;
+mod raycast;
+mod selection_functions;
+mod spatial_join;
+mod split;
+mod statistics;
+
+pub use self::aggregate::{Aggregator, Count};
+pub use self::approximate::ApproximateNearestNeighborIterator;
+pub use self::closest_pairs::ClosestPairIterator;
+pub use self::handles::{HandleEntry, HandleRTree, RTreeHandle};
+pub use self::metric::{
+    Chebyshev, Euclidean, Manhattan, Metric, SelectWithinDistance, WeightedEuclidean,
+    WeightedManhattan,
+};
+pub use self::node_ref::{DirectoryChildrenIterator, DirectoryNodeRef, RTreeNodeRef};
+pub use self::object_mut::RTreeObjectMut;
+pub use self::packed::{PackedNearestNeighborIterator, PackedRTree, PackedSelectionIterator};
+pub use self::persistent::{
+    PersistentNearestNeighborIterator, PersistentRTree, PersistentSelectionIterator,
+};
+pub use self::selection_functions::{
+    SelectAll, SelectAtPoint, SelectInCircle, SelectInRectangle, SelectIntersectingEdge,
+    SelectionFunction, SelectionIterator, SelectionIteratorMut,
+};
+pub use self::spatial_join::SpatialJoinIterator;
+pub use self::split::SplitStrategy;
+pub use self::statistics::{LevelStatistics, RTreeStatistics};
+
+use self::aggregate::NodeAggregate;
+
+use crate::boundingrect::BoundingRect;
+use crate::misc::min_inline;
+use crate::point_traits::{PointN, PointNExtensions};
+use crate::traits::SpatialObject;
+use num::zero;
+use std::iter::Once;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
+
+#[cfg(feature = "serde_serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Parameters of an r-tree.
+///
+/// The default options are suitable for most use cases. Nodes contain between
+/// `min_size` and `max_size` children, overfull nodes first try to reinsert
+/// `reinsertion_count` of their children before being split.
+///
+/// The builder methods panic on invalid combinations. The `try_` variants return an
+/// `RTreeOptionsError` instead, `validate` checks options that were created otherwise,
+/// e.g. by deserialization.
+///
+/// # Example
+/// ```
+/// use spade::rtree::{RTree, RTreeOptions};
+/// use cgmath::Point2;
+///
+/// # fn main() -> Result<(), spade::rtree::RTreeOptionsError> {
+/// let options = RTreeOptions::new()
+///     .try_max_size(12)?
+///     .try_min_size(5)?
+///     .try_reinsertion_count(4)?;
+/// let tree: RTree<Point2<f32>> = options.build();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde_serialize", serde(default))]
+pub struct RTreeOptions {
+    max_size: usize,
+    min_size: usize,
+    reinsertion_count: usize,
+    split_strategy: SplitStrategy,
+    forced_reinsertion: bool,
+    bulk_load_size: Option<usize>,
+}
+
+impl Default for RTreeOptions {
+    fn default() -> RTreeOptions {
+        RTreeOptions::new()
+    }
+}
+
+impl RTreeOptions {
+    /// Creates the default options.
+    ///
+    /// Nodes contain between 3 and 6 children, 2 of which are reinserted on overflow.
+    pub fn new() -> Self {
+        RTreeOptions {
+            max_size: 6,
+            min_size: 3,
+            reinsertion_count: 2,
+            split_strategy: SplitStrategy::RStar,
+            forced_reinsertion: true,
+            bulk_load_size: None,
+        }
+    }
+
+    /// Sets the maximal number of children of a node.
+    ///
+    /// # Panics
+    /// Panics if the resulting options are invalid, see `try_max_size`.
+    pub fn max_size(self, max_size: usize) -> Self {
+        self.try_max_size(max_size)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Sets the maximal number of children of a node.
+    ///
+    /// Fails if `max_size` is not larger than the minimal size and the reinsertion count,
+    /// or if it is smaller than the bulk load size.
+    pub fn try_max_size(self, max_size: usize) -> Result<Self, RTreeOptionsError> {
+        let result = RTreeOptions { max_size, ..self };
+        result.validate().map(|_| result)
+    }
+
+    /// Sets the minimal number of children of a node.
+    ///
+    /// # Panics
+    /// Panics if the resulting options are invalid, see `try_min_size`.
+    pub fn min_size(self, min_size: usize) -> Self {
+        self.try_min_size(min_size)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Sets the minimal number of children of a node.
+    ///
+    /// Fails if `min_size` is not smaller than the maximal size or if it is larger than the
+    /// bulk load size.
+    pub fn try_min_size(self, min_size: usize) -> Result<Self, RTreeOptionsError> {
+        let result = RTreeOptions { min_size, ..self };
+        result.validate().map(|_| result)
+    }
+
+    /// Sets the number of children that are reinserted when a node overflows.
+    ///
+    /// # Panics
+    /// Panics if the resulting options are invalid, see `try_reinsertion_count`.
+    pub fn reinsertion_count(self, reinsertion_count: usize) -> Self {
+        self.try_reinsertion_count(reinsertion_count)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Sets the number of children that are reinserted when a node overflows.
+    ///
+    /// Fails if `reinsertion_count` is zero or not smaller than the maximal size.
+    pub fn try_reinsertion_count(
+        self,
+        reinsertion_count: usize,
+    ) -> Result<Self, RTreeOptionsError> {
+        let result = RTreeOptions {
+            reinsertion_count,
+            ..self
+        };
+        result.validate().map(|_| result)
+    }
+
+    /// Sets the number of children of the nodes created by bulk loading.
+    ///
+    /// Defaults to the maximal size, yielding completely filled nodes. A smaller value
+    /// leaves room for later insertions before nodes need to be split.
+    ///
+    /// # Panics
+    /// Panics if the resulting options are invalid, see `try_bulk_load_size`.
+    pub fn bulk_load_size(self, bulk_load_size: usize) -> Self {
+        self.try_bulk_load_size(bulk_load_size)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Sets the number of children of the nodes created by bulk loading.
+    ///
+    /// Fails if `bulk_load_size` is smaller than the minimal size or two, or if it is larger
+    /// than the maximal size.
+    pub fn try_bulk_load_size(self, bulk_load_size: usize) -> Result<Self, RTreeOptionsError> {
+        let result = RTreeOptions {
+            bulk_load_size: Some(bulk_load_size),
+            ..self
+        };
+        result.validate().map(|_| result)
+    }
+
+    /// Sets the algorithm used to split overfull nodes.
+    pub fn split_strategy(mut self, split_strategy: SplitStrategy) -> Self {
+        self.split_strategy = split_strategy;
+        self
+    }
+
+    /// Enables or disables the r*-tree's forced reinsertion.
+    ///
+    /// If disabled, overfull nodes are split immediately. This makes insertion
+    /// cheaper but usually yields a tree with more overlap.
+    pub fn forced_reinsertion(mut self, forced_reinsertion: bool) -> Self {
+        self.forced_reinsertion = forced_reinsertion;
+        self
+    }
+
+    /// Checks if all parameters are consistent.
+    ///
+    /// Options created with the builder methods are always valid. Use this method to
+    /// check options from other sources, e.g. a deserialized configuration, before
+    /// creating a tree with them.
+    pub fn validate(&self) -> Result<(), RTreeOptionsError> {
+        if self.max_size <= self.min_size {
+            return Err(RTreeOptionsError::MaxSizeNotLargerThanMinSize {
+                max_size: self.max_size,
+                min_size: self.min_size,
+            });
+        }
+        if self.reinsertion_count == 0 {
+            return Err(RTreeOptionsError::ZeroReinsertionCount);
+        }
+        if self.reinsertion_count >= self.max_size {
+            return Err(RTreeOptionsError::ReinsertionCountTooLarge {
+                reinsertion_count: self.reinsertion_count,
+                max_size: self.max_size,
+            });
+        }
+        match self.bulk_load_size {
+            Some(bulk_load_size)
+                if bulk_load_size < ::std::cmp::max(self.min_size, 2)
+                    || bulk_load_size > self.max_size =>
+            {
+                Err(RTreeOptionsError::BulkLoadSizeOutOfRange {
+                    bulk_load_size,
+                    min_size: self.min_size,
+                    max_size: self.max_size,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Creates an empty r-tree with these options.
+    pub fn build<T: SpatialObject>(self) -> RTree<T> {
+        RTree::new_with_options(self)
+    }
+
+    fn bulk_load_node_size(&self) -> usize {
+        self.bulk_load_size.unwrap_or(self.max_size)
+    }
+
+    /// Returns `true` if both options insert and remove objects in the same way, i.e. if
+    /// they differ at most in their bulk load size.
+    fn has_same_insertion_parameters(&self, other: &RTreeOptions) -> bool {
+        RTreeOptions {
+            bulk_load_size: None,
+            ..*self
+        } == RTreeOptions {
+            bulk_load_size: None,
+            ..*other
+        }
+    }
+}
+
+/// An error returned when creating or validating invalid `RTreeOptions`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RTreeOptionsError {
+    /// The maximal node size must be larger than the minimal node size.
+    MaxSizeNotLargerThanMinSize {
+        /// The invalid maximal node size.
+        max_size: usize,
+        /// The invalid minimal node size.
+        min_size: usize,
+    },
+    /// The reinsertion count must not be zero.
+    ZeroReinsertionCount,
+    /// The reinsertion count must be smaller than the maximal node size.
+    ReinsertionCountTooLarge {
+        /// The invalid reinsertion count.
+        reinsertion_count: usize,
+        /// The maximal node size.
+        max_size: usize,
+    },
+    /// The bulk load node size must be at least the minimal node size and two, and at most
+    /// the maximal node size.
+    BulkLoadSizeOutOfRange {
+        /// The invalid bulk load node size.
+        bulk_load_size: usize,
+        /// The minimal node size.
+        min_size: usize,
+        /// The maximal node size.
+        max_size: usize,
+    },
+}
+
+impl ::std::fmt::Display for RTreeOptionsError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            RTreeOptionsError::MaxSizeNotLargerThanMinSize { max_size, min_size } => write!(
+                f,
+                "Max size ({}) must be larger than min size ({})",
+                max_size, min_size
+            ),
+            RTreeOptionsError::ZeroReinsertionCount => write!(f, "Reinsertion cannot be zero"),
+            RTreeOptionsError::ReinsertionCountTooLarge {
+                reinsertion_count,
+                max_size,
+            } => write!(
+                f,
+                "Reinsertion count ({}) must be smaller than max size ({})",
+                reinsertion_count, max_size
+            ),
+            RTreeOptionsError::BulkLoadSizeOutOfRange {
+                bulk_load_size,
+                min_size,
+                max_size,
+            } => write!(
+                f,
+                "Bulk load size ({}) must be at least 2 and between min size ({}) and max size ({})",
+                bulk_load_size, min_size, max_size
+            ),
+        }
+    }
+}
+
+impl ::std::error::Error for RTreeOptionsError {}
+
+/// Iterates over all entries in an r-tree.
+/// Returned by `RTree::iter()`
+pub struct RTreeIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    data: &'a DirectoryNodeData<T>,
+    cur_index: usize,
+    cur_iterator: Option<Box<RTreeNodeIterator<'a, T>>>,
+}
+
+#[allow(missing_docs)]
+pub enum RTreeNodeIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    LeafIterator(Once<&'a T>),
+    DirectoryNodeIterator(RTreeIterator<'a, T>),
+}
+
+impl<'a, T> RTreeIterator<'a, T>
+where
+    T: SpatialObject,
+{
+    fn new(data: &'a DirectoryNodeData<T>) -> RTreeIterator<'a, T> {
+        RTreeIterator {
+            data,
+            cur_index: 0,
+            cur_iterator: data
+                .children
+                .first()
+                .map(|child| Box::new(RTreeNodeIterator::new(child))),
+        }
+    }
+}
+
+impl<'a, T> Iterator for RTreeIterator<'a, T>
+where
+    T: SpatialObject,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if let Some(cur_iterator) = self.cur_iterator.as_mut() {
+            if let Some(next) = cur_iterator.next() {
+                // Child iterator can still iterate
+                Some(next)
+            } else {
+                loop {
+                    // Change to the next child
+                    self.cur_index += 1;
+                    if let Some(child_node) = self.data.children.get(self.cur_index) {
+                        // Set a new iterator...
+                        *cur_iterator = Box::new(RTreeNodeIterator::new(child_node));
+                        // ... and call it
+                        let next = cur_iterator.next();
+                        if next.is_some() {
+                            return next;
+                        }
+                    } else {
+                        // We've iterated through all of our children
+                        return None;
+                    }
+                }
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T> RTreeNodeIterator<'a, T>
+where
+    T: SpatialObject,
+{
+    fn new(node: &'a RTreeNode<T>) -> RTreeNodeIterator<'a, T> {
+        use self::RTreeNodeIterator::{DirectoryNodeIterator, LeafIterator};
+        match node {
+            RTreeNode::Leaf(ref b) => LeafIterator(::std::iter::once(b)),
+            RTreeNode::DirectoryNode(ref data) => DirectoryNodeIterator(RTreeIterator::new(data)),
+        }
+    }
+}
+
+impl<'a, T> Iterator for RTreeNodeIterator<'a, T>
+where
+    T: SpatialObject,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        use self::RTreeNodeIterator::{DirectoryNodeIterator, LeafIterator};
+        match self {
+            LeafIterator(ref mut once) => once.next(),
+            DirectoryNodeIterator(ref mut iter) => iter.next(),
+        }
+    }
+}
+
+/// An iterator yielding the elements of an `RTree` ordered by their distance to a query point.
+///
+/// This `struct` is created by the `nearest_neighbor_iter` and
+/// `nearest_neighbor_iterator_with_metric` methods on `RTree`.
+pub struct NearestNeighborIterator<'a, T, M = Euclidean>
+where
+    T: SpatialObject + 'a,
+    M: Metric<T>,
+{
+    inner: NearestNeighborDistance2Iterator<'a, T, M>,
+}
+
+/// An iterator yielding the elements of an `RTree` together with their squared distance
+/// to a query point, ordered by distance.
+///
+/// This `struct` is created by the `nearest_neighbor_iter_with_distance2` method on `RTree`.
+/// If a `Metric` other than `Euclidean` is used, the metric's distance is yielded instead.
+pub struct NearestNeighborDistance2Iterator<'a, T, M = Euclidean>
+where
+    T: SpatialObject + 'a,
+    M: Metric<T>,
+{
+    inner: EntryDistanceIterator<'a, RTreeNode<T>, M>,
+}
+
+/// An entry of a priority queue that is ordered by ascending distance.
+///
+/// `BinaryHeap` is a max heap, the inverse comparison makes it pop the entry with the
+/// smallest distance first. Used by all best-first traversals of the different tree
+/// types.
+struct DistanceWrapper<N, S> {
+    node: N,
+    distance: S,
+}
+
+type RTreeNodeDistanceWrapper<'a, T> =
+    DistanceWrapper<&'a RTreeNode<T>, <<T as SpatialObject>::Point as PointN>::Scalar>;
+
+impl<N, S: PartialOrd> PartialEq for DistanceWrapper<N, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl<N, S: PartialOrd> PartialOrd for DistanceWrapper<N, S> {
+    fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N, S: PartialOrd> Eq for DistanceWrapper<N, S> {}
+
+impl<N, S: PartialOrd> Ord for DistanceWrapper<N, S> {
+    fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+        // Inverse comparison creates a min heap
+        other.distance.partial_cmp(&self.distance).unwrap()
+    }
+}
+
+/// Read access to an entry of a tree, i.e. an object or a directory node.
+///
+/// Implemented by the entries of `RTree` and `PersistentRTree` to share their query
+/// iterators.
+trait TreeEntry: Sized {
+    type Object: SpatialObject;
+
+    /// Returns the contained object or the bounding rectangle and children of a node.
+    fn entry(&self) -> EntryRef<'_, Self>;
+}
+
+enum EntryRef<'a, E>
+where
+    E: TreeEntry,
+{
+    Leaf(&'a E::Object),
+    Node(
+        &'a BoundingRect<<E::Object as SpatialObject>::Point>,
+        &'a [E],
+    ),
+}
+
+impl<T> TreeEntry for RTreeNode<T>
+where
+    T: SpatialObject,
+{
+    type Object = T;
+
+    fn entry(&self) -> EntryRef<'_, Self> {
+        match self {
+            RTreeNode::Leaf(ref t) => EntryRef::Leaf(t),
+            RTreeNode::DirectoryNode(ref data) => {
+                EntryRef::Node(data.bounding_box.as_ref().unwrap(), &data.children)
+            }
+        }
+    }
+}
+
+/// Yields the objects below a list of entries together with their distance to a query
+/// point, ordered by distance.
+struct EntryDistanceIterator<'a, E, M>
+where
+    E: TreeEntry,
+{
+    nodes: ::std::collections::BinaryHeap<
+        DistanceWrapper<&'a E, <<E::Object as SpatialObject>::Point as PointN>::Scalar>,
+    >,
+    query_point: <E::Object as SpatialObject>::Point,
+    max_distance2: Option<<<E::Object as SpatialObject>::Point as PointN>::Scalar>,
+    metric: M,
+}
+
+impl<'a, E, M> EntryDistanceIterator<'a, E, M>
+where
+    E: TreeEntry,
+    M: Metric<E::Object>,
+{
+    fn new(
+        children: &'a [E],
+        query_point: <E::Object as SpatialObject>::Point,
+        max_distance2: Option<<<E::Object as SpatialObject>::Point as PointN>::Scalar>,
+        metric: M,
+    ) -> Self {
+        let mut result = EntryDistanceIterator {
+            nodes: Default::default(),
+            query_point,
+            max_distance2,
+            metric,
+        };
+        result.extend_heap(children);
+        result
+    }
+
+    fn extend_heap(&mut self, children: &'a [E]) {
+        let query_point = &self.query_point;
+        let max_distance2 = &self.max_distance2;
+        let metric = &self.metric;
+        self.nodes.extend(children.iter().filter_map(|child| {
+            let distance = match child.entry() {
+                EntryRef::Node(mbr, _) => metric.min_distance(mbr, query_point),
+                EntryRef::Leaf(t) => metric.distance(t, query_point),
+            };
+            match max_distance2 {
+                Some(ref max_distance2) if distance > *max_distance2 => None,
+                _ => Some(DistanceWrapper {
+                    node: child,
+                    distance,
+                }),
+            }
+        }));
+    }
+}
+
+impl<'a, E, M> Iterator for EntryDistanceIterator<'a, E, M>
+where
+    E: TreeEntry,
+    M: Metric<E::Object>,
+{
+    type Item = (
+        &'a E::Object,
+        <<E::Object as SpatialObject>::Point as PointN>::Scalar,
+    );
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(DistanceWrapper { node, distance }) = self.nodes.pop() {
+            match node.entry() {
+                EntryRef::Node(_, children) => self.extend_heap(children),
+                EntryRef::Leaf(t) => return Some((t, distance)),
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T, M> NearestNeighborIterator<'a, T, M>
+where
+    T: SpatialObject + 'a,
+    M: Metric<T>,
+{
+    fn new(root: &'a DirectoryNodeData<T>, query_point: T::Point, metric: M) -> Self {
+        NearestNeighborIterator {
+            inner: NearestNeighborDistance2Iterator::new(root, query_point, None, metric),
+        }
+    }
+}
+
+impl<'a, T, M> Iterator for NearestNeighborIterator<'a, T, M>
+where
+    T: SpatialObject + 'a,
+    M: Metric<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(t, _)| t)
+    }
+}
+
+impl<'a, T, M> NearestNeighborDistance2Iterator<'a, T, M>
+where
+    T: SpatialObject + 'a,
+    M: Metric<T>,
+{
+    fn new(
+        root: &'a DirectoryNodeData<T>,
+        query_point: T::Point,
+        max_distance2: Option<<T::Point as PointN>::Scalar>,
+        metric: M,
+    ) -> Self {
+        NearestNeighborDistance2Iterator {
+            inner: EntryDistanceIterator::new(&root.children, query_point, max_distance2, metric),
+        }
+    }
+}
+
+impl<'a, T, M> Iterator for NearestNeighborDistance2Iterator<'a, T, M>
+where
+    T: SpatialObject + 'a,
+    M: Metric<T>,
+{
+    type Item = (&'a T, <T::Point as PointN>::Scalar);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<T> DirectoryNodeData<T>
+where
+    T: SpatialObject + Clone,
+{
+    fn bulk_load(options: Arc<RTreeOptions>, elements: &mut [T]) -> DirectoryNodeData<T> {
+        let m = options.bulk_load_node_size();
+        let mut depth = 1;
+        while m.pow(depth as u32) < elements.len() {
+            depth += 1;
+        }
+        Self::bulk_load_with_depth(options, elements, depth)
+    }
+
+    fn bulk_load_with_depth(
+        options: Arc<RTreeOptions>,
+        elements: &mut [T],
+        depth: usize,
+    ) -> DirectoryNodeData<T> {
+        if depth == 1 {
+            // Reached leaf level
+            let elements: Vec<_> = elements
+                .iter_mut()
+                .map(|e| RTreeNode::Leaf(e.clone()))
+                .collect();
+            return DirectoryNodeData::new_parent(elements, 1, options);
+        }
+
+        let n_subtree = options.bulk_load_node_size().pow(depth as u32 - 1);
+        let num_clusters = elements.len().div_ceil(n_subtree);
+        let cluster_num_elements = elements.len().div_ceil(num_clusters);
+
+        let mut children = Vec::with_capacity(options.max_size + 1);
+        Self::bulk_load_partition(
+            &options,
+            elements,
+            num_clusters,
+            cluster_num_elements,
+            0,
+            depth - 1,
+            &mut children,
+        );
+        DirectoryNodeData::new_parent(children, depth, options)
+    }
+
+    /// Partitions `elements` into clusters along all axes, starting at `axis`.
+    ///
+    /// The elements are cut into slices along `axis`, each slice is then recursively
+    /// partitioned along the remaining axes. Each cluster of the last axis is loaded
+    /// into a subtree of depth `child_depth`.
+    fn bulk_load_partition(
+        options: &Arc<RTreeOptions>,
+        elements: &mut [T],
+        num_clusters: usize,
+        cluster_num_elements: usize,
+        axis: usize,
+        child_depth: usize,
+        children: &mut Vec<RTreeNode<T>>,
+    ) {
+        let remaining_axes = T::Point::dimensions() - axis;
+        if remaining_axes == 1 {
+            create_clusters(elements, cluster_num_elements, axis);
+            for cluster in elements.chunks_mut(cluster_num_elements) {
+                let child =
+                    DirectoryNodeData::bulk_load_with_depth(options.clone(), cluster, child_depth);
+                children.push(RTreeNode::DirectoryNode(child));
+            }
+            return;
+        }
+
+        let num_slices = nth_root_ceil(num_clusters, remaining_axes);
+        let num_clusters_per_slice = num_clusters.div_ceil(num_slices);
+        let slice_num_elements = num_clusters_per_slice * cluster_num_elements;
+        create_clusters(elements, slice_num_elements, axis);
+        for slice in elements.chunks_mut(slice_num_elements) {
+            Self::bulk_load_partition(
+                options,
+                slice,
+                num_clusters_per_slice,
+                cluster_num_elements,
+                axis + 1,
+                child_depth,
+                children,
+            );
+        }
+    }
+}
+
+#[doc(hidden)]
+impl<T> DirectoryNodeData<T>
+where
+    T: SpatialObject,
+{
+    pub fn children(&self) -> &Vec<RTreeNode<T>> {
+        &self.children
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn mbr(&self) -> BoundingRect<T::Point> {
+        self.bounding_box.clone().unwrap()
+    }
+
+    fn new(depth: usize, options: Arc<RTreeOptions>) -> DirectoryNodeData<T> {
+        DirectoryNodeData {
+            bounding_box: None,
+            children: Vec::with_capacity(options.max_size + 1),
+            options,
+            depth,
+            aggregate: None,
+            id: NodeId::new(),
+        }
+    }
+
+    #[inline]
+    fn new_parent(
+        children: Vec<RTreeNode<T>>,
+        depth: usize,
+        options: Arc<RTreeOptions>,
+    ) -> DirectoryNodeData<T> {
+        let mut result = DirectoryNodeData {
+            bounding_box: None,
+            children,
+            depth,
+            options,
+            aggregate: None,
+            id: NodeId::new(),
+        };
+        result.update_mbr();
+        result
+    }
+
+    #[inline]
+    fn update_mbr(&mut self) {
+        if let Some(first) = self.children.first() {
+            let mut new_mbr = first.mbr();
+            for child in &self.children[1..] {
+                new_mbr.add_rect(&child.mbr());
+            }
+            self.bounding_box = Some(new_mbr);
+        } else {
+            self.bounding_box = None;
+        }
+        self.update_aggregate();
+    }
+
+    #[inline]
+    fn update_mbr_with_element(&mut self, element_bb: &BoundingRect<T::Point>) {
+        if let Some(ref mut bb) = self.bounding_box {
+            bb.add_rect(element_bb);
+        } else {
+            self.bounding_box = Some(element_bb.clone());
+        }
+    }
+
+    fn insert<O>(
+        &mut self,
+        t: RTreeNode<T>,
+        state: &mut InsertionState,
+        observer: &mut O,
+    ) -> InsertionResult<T>
+    where
+        O: NodeObserver<T>,
+    {
+        // Adjust own mbr - the element will most likely become a child of this node
+        self.update_mbr_with_element(&t.mbr());
+        self.add_to_aggregate(&t);
+        if t.depth() + 1 == self.depth {
+            // Force insertion into this node
+            self.add_observed_child(t, observer);
+            return self.resolve_overflow(state, observer);
+        }
+        let expand = {
+            let follow = self.choose_subtree(&t);
+            follow.insert(t, state, observer)
+        };
+        match expand {
+            InsertionResult::Split(child) => {
+                // Insert into own list
+                self.add_observed_child(child, observer);
+                self.resolve_overflow(state, observer)
+            }
+            result @ InsertionResult::Reinsert(_) => {
+                // Reinsertion can shrink the mbr
+                self.update_mbr();
+                result
+            }
+            complete => complete,
+        }
+    }
+
+    fn add_observed_child<O>(&mut self, child: RTreeNode<T>, observer: &mut O)
+    where
+        O: NodeObserver<T>,
+    {
+        self.add_children(vec![child]);
+        observer.added(self.children.last().unwrap(), self.id);
+    }
+
+    fn resolve_overflow<O>(
+        &mut self,
+        state: &mut InsertionState,
+        observer: &mut O,
+    ) -> InsertionResult<T>
+    where
+        O: NodeObserver<T>,
+    {
+        if self.children.len() > self.options.max_size {
+            if !self.options.forced_reinsertion || state.did_reinsert(self.depth) {
+                // We did already reinsert on that level - split this node
+                let offsplit = self.split();
+                if let RTreeNode::DirectoryNode(ref data) = offsplit {
+                    for child in &data.children {
+                        observer.added(child, data.id);
+                    }
+                }
+                InsertionResult::Split(offsplit)
+            } else {
+                // We didn't attempt to reinsert yet - give it a try
+                state.mark_reinsertion(self.depth);
+                let reinsertion_nodes = self.reinsert();
+                InsertionResult::Reinsert(reinsertion_nodes)
+            }
+        } else {
+            InsertionResult::Complete
+        }
+    }
+
+    fn split(&mut self) -> RTreeNode<T> {
+        let offsplit = match self.options.split_strategy {
+            SplitStrategy::RStar => self.rstar_split(),
+            SplitStrategy::Quadratic => {
+                split::quadratic_split(&mut self.children, self.options.min_size)
+            }
+            SplitStrategy::Linear => split::linear_split(&mut self.children, self.options.min_size),
+        };
+        let mut result = DirectoryNodeData::new_parent(offsplit, self.depth, self.options.clone());
+        result.set_node_aggregator(self.aggregator());
+        self.update_mbr();
+        RTreeNode::DirectoryNode(result)
+    }
+
+    fn rstar_split(&mut self) -> Vec<RTreeNode<T>> {
+        let axis = self.get_split_axis();
+        assert!(self.children.len() >= 2);
+        // Sort along axis
+        self.children.sort_by(|l, r| {
+            l.mbr()
+                .lower()
+                .nth(axis)
+                .partial_cmp(&r.mbr().lower().nth(axis))
+                .unwrap()
+        });
+        let mut best = (zero(), zero());
+        let mut best_index = self.options.min_size;
+
+        for k in self.options.min_size..=self.children.len() - self.options.min_size {
+            let mut first_mbr = self.children[k - 1].mbr();
+            let mut second_mbr = self.children[k].mbr();
+            let (l, r) = self.children.split_at(k);
+            for child in l {
+                first_mbr.add_rect(&child.mbr());
+            }
+            for child in r {
+                second_mbr.add_rect(&child.mbr());
+            }
+
+            let overlap_value = first_mbr.intersect(&second_mbr).area();
+            let area_value = first_mbr.area() + second_mbr.area();
+            let new_best = (overlap_value, area_value);
+            if new_best < best || k == self.options.min_size {
+                best = new_best;
+                best_index = k;
+            }
+        }
+        self.children.split_off(best_index)
+    }
+
+    fn reinsert(&mut self) -> Vec<RTreeNode<T>> {
+        let center = self.mbr().center();
+        // Sort with increasing order so we can use Vec::split_off
+        self.children.sort_by(|l, r| {
+            let l_center = l.mbr().center();
+            let r_center = r.mbr().center();
+            l_center
+                .sub(&center)
+                .length2()
+                .partial_cmp(&(r_center.sub(&center)).length2())
+                .unwrap()
+        });
+        let num_children = self.children.len();
+        let result = self
+            .children
+            .split_off(num_children - self.options.reinsertion_count);
+        self.update_mbr();
+        result
+    }
+
+    fn get_split_axis(&mut self) -> usize {
+        let mut best_goodness = zero();
+        let mut best_axis = 0;
+        for axis in 0..T::Point::dimensions() {
+            // Sort children along the current axis
+            self.children.sort_by(|l, r| {
+                l.mbr()
+                    .lower()
+                    .nth(axis)
+                    .partial_cmp(&r.mbr().lower().nth(axis))
+                    .unwrap()
+            });
+            for k in self.options.min_size..=self.children.len() - self.options.min_size {
+                let mut first_mbr = self.children[k - 1].mbr();
+                let mut second_mbr = self.children[k].mbr();
+                let (l, r) = self.children.split_at(k);
+                for child in l {
+                    first_mbr.add_rect(&child.mbr());
+                }
+                for child in r {
+                    second_mbr.add_rect(&child.mbr());
+                }
+
+                let margin_value = first_mbr.half_margin() + second_mbr.half_margin();
+                if best_goodness > margin_value || axis == 0 {
+                    best_axis = axis;
+                    best_goodness = margin_value;
+                }
+            }
+        }
+        best_axis
+    }
+
+    fn choose_subtree(&mut self, node: &RTreeNode<T>) -> &mut DirectoryNodeData<T> {
+        assert!(self.depth >= 2, "Cannot choose subtree on this level");
+        let insertion_mbr = node.mbr();
+        let mut inclusion_count = 0;
+        let mut min_area = zero();
+        let mut min_index = 0;
+        let mut first = true;
+        for (index, child) in self.children.iter().enumerate() {
+            let mbr = child.mbr();
+            if mbr.contains_rect(&insertion_mbr) {
+                inclusion_count += 1;
+                let area = mbr.area();
+                if area < min_area || first {
+                    min_area = area;
+                    min_index = index;
+                    first = false;
+                }
+            }
+        }
+        if inclusion_count == 0 {
+            // No inclusion found, subtree depends on overlap and area increase
+            let all_leaves = self.depth <= 2;
+            let mut min = (zero(), zero(), zero());
+
+            for (index, child1) in self.children.iter().enumerate() {
+                let mbr = child1.mbr();
+                let mut new_mbr = mbr.clone();
+                new_mbr.add_rect(&insertion_mbr);
+                let overlap_increase = if all_leaves {
+                    // Calculate minimal overlap increase
+                    let mut overlap: <T::Point as PointN>::Scalar = zero();
+                    let mut new_overlap: <T::Point as PointN>::Scalar = zero();
+                    for child2 in self.children.iter() {
+                        if child1 as *const _ != child2 as *const _ {
+                            let child_mbr = child2.mbr();
+                            overlap = overlap.clone() + mbr.intersect(&child_mbr).area();
+                            new_overlap =
+                                new_overlap.clone() + new_mbr.intersect(&child_mbr).area();
+                        }
+                    }
+                    new_overlap - overlap
+                } else {
+                    // Don't calculate overlap increase if not all children are leaves
+                    zero()
+                };
+                // Calculate area increase and area
+                let area = new_mbr.area();
+                let area_increase = area.clone() - mbr.area();
+                let new_min = (overlap_increase, area_increase, area);
+                if new_min < min || index == 0 {
+                    min = new_min;
+                    min_index = index;
+                }
+            }
+        }
+        if let RTreeNode::DirectoryNode(ref mut data) = self.children[min_index] {
+            data
+        } else {
+            panic!("There must not be leaves on this depth")
+        }
+    }
+
+    fn add_children(&mut self, mut new_children: Vec<RTreeNode<T>>) {
+        if let Some(ref mut bb) = &mut self.bounding_box {
+            for child in &new_children {
+                bb.add_rect(&child.mbr());
+            }
+            self.children.append(&mut new_children);
+            return;
+        }
+        if let Some(first) = new_children.first() {
+            let mut bb = first.mbr();
+            for child in &new_children[1..] {
+                bb.add_rect(&child.mbr());
+            }
+            self.bounding_box = Some(bb);
+        }
+        self.children.append(&mut new_children);
+    }
+
+    fn into_leaves(self, result: &mut Vec<T>) {
+        for child in self.children {
+            match child {
+                RTreeNode::DirectoryNode(data) => data.into_leaves(result),
+                RTreeNode::Leaf(t) => result.push(t),
+            }
+        }
+    }
+
+    fn close_neighbor(&self, point: &T::Point) -> Option<&T> {
+        if self.children.is_empty() {
+            return None;
+        }
+        let mut follow = self;
+        loop {
+            let mut min_min_dist = zero();
+            let mut new_follow = follow.children.first().unwrap();
+            let mut first = true;
+            for child in follow.children.iter() {
+                let min_dist = child.mbr().min_dist2(point);
+                if min_dist < min_min_dist || first {
+                    new_follow = child;
+                    min_min_dist = min_dist;
+                    first = false;
+                }
+            }
+            match new_follow {
+                RTreeNode::DirectoryNode(ref data) => {
+                    follow = data;
+                }
+                RTreeNode::Leaf(ref t) => return Some(t),
+            }
+        }
+    }
+
+    fn extend_heap<'a>(
+        heap: &mut ::std::collections::BinaryHeap<RTreeNodeDistanceWrapper<'a, T>>,
+        children: &'a [RTreeNode<T>],
+        query_point: &T::Point,
+        prune_distance_option: &mut Option<<T::Point as PointN>::Scalar>,
+    ) {
+        for child in children {
+            let distance = match child {
+                RTreeNode::DirectoryNode(ref data) => data.mbr().min_dist2(query_point),
+                RTreeNode::Leaf(ref t) => t.distance2(query_point),
+            };
+            let do_prune;
+            if let Some(prune_distance) = prune_distance_option.clone() {
+                if distance > prune_distance {
+                    do_prune = true;
+                } else {
+                    let new_prune_distance = child.mbr().min_max_dist2(query_point);
+                    *prune_distance_option = Some(min_inline(prune_distance, new_prune_distance));
+                    do_prune = false;
+                }
+            } else {
+                *prune_distance_option = Some(child.mbr().min_max_dist2(query_point));
+                do_prune = false;
+            }
+            if !do_prune {
+                heap.push(RTreeNodeDistanceWrapper {
+                    distance,
+                    node: child,
+                });
+            }
+        }
+    }
+
+    fn nearest_neighbor(&self, point: &T::Point) -> Option<&T> {
+        let mut smallest_min_max = None;
+        let mut heap = ::std::collections::binary_heap::BinaryHeap::new();
+        Self::extend_heap(&mut heap, &self.children, point, &mut smallest_min_max);
+        while let Some(current) = heap.pop() {
+            match current {
+                RTreeNodeDistanceWrapper {
+                    node: RTreeNode::DirectoryNode(ref data),
+                    ..
+                } => {
+                    Self::extend_heap(&mut heap, &data.children, point, &mut smallest_min_max);
+                }
+                RTreeNodeDistanceWrapper {
+                    node: RTreeNode::Leaf(ref t),
+                    ..
+                } => {
+                    return Some(t);
+                }
+            }
+        }
+        None
+    }
+
+    fn nearest_neighbors<'a>(
+        &'a self,
+        point: &T::Point,
+        mut nearest_distance: Option<<T::Point as PointN>::Scalar>,
+        result: &mut Vec<&'a T>,
+    ) -> Option<<T::Point as PointN>::Scalar> {
+        // Calculate smallest minmax-distance
+        let mut smallest_min_max: <T::Point as PointN>::Scalar = zero();
+        let mut first = true;
+        for child in self.children.iter() {
+            let new_min = child.mbr().min_max_dist2(point);
+            if first {
+                first = false;
+                smallest_min_max = new_min;
+            } else {
+                smallest_min_max = min_inline(smallest_min_max, new_min);
+            }
+        }
+        let mut sorted: Vec<_> = self.children.iter().collect();
+        sorted.sort_by(|l, r| {
+            l.mbr()
+                .min_dist2(point)
+                .partial_cmp(&r.mbr().min_dist2(point))
+                .unwrap()
+        });
+        for child in sorted.iter() {
+            let min_dist = child.mbr().min_dist2(point);
+            if min_dist > smallest_min_max
+                || nearest_distance
+                    .clone()
+                    .map(|d| min_dist > d)
+                    .unwrap_or(false)
+            {
+                // Prune this element
+                continue;
+            }
+            if let Some(nearest) = child.nearest_neighbors(point, nearest_distance.clone(), result)
+            {
+                nearest_distance = Some(nearest);
+            }
+        }
+        nearest_distance
+    }
+
+    /// Removes all objects matched by `matches` from the subtrees selected by `should_descend`.
+    ///
+    /// Removed objects are appended to `removed`. Child nodes that become underfull are
+    /// dissolved, their remaining children are appended to `orphans` and must be reinserted.
+    /// Returns `true` if this node has been changed.
+    fn drain_where<D, M>(
+        &mut self,
+        should_descend: &D,
+        matches: &mut M,
+        removed: &mut Vec<T>,
+        orphans: &mut Vec<RTreeNode<T>>,
+    ) -> bool
+    where
+        D: Fn(&BoundingRect<T::Point>) -> bool,
+        M: FnMut(&T) -> bool,
+    {
+        let min_size = self.options.min_size;
+        let mut changed = false;
+        let mut index = 0;
+        while index < self.children.len() {
+            let remove_child = match self.children[index] {
+                RTreeNode::Leaf(ref t) => matches(t),
+                RTreeNode::DirectoryNode(ref mut data) => {
+                    if should_descend(&data.mbr())
+                        && data.drain_where(should_descend, matches, removed, orphans)
+                    {
+                        changed = true;
+                        data.children.is_empty() || data.children.len() < min_size
+                    } else {
+                        false
+                    }
+                }
+            };
+            if remove_child {
+                changed = true;
+                match self.children.swap_remove(index) {
+                    RTreeNode::Leaf(t) => removed.push(t),
+                    RTreeNode::DirectoryNode(data) => orphans.extend(data.children),
+                }
+            } else {
+                index += 1;
+            }
+        }
+        if changed {
+            self.update_mbr();
+        }
+        changed
+    }
+
+    /// Returns the child directory node with a given id.
+    fn child_node(&self, id: NodeId) -> Option<&DirectoryNodeData<T>> {
+        self.children.iter().find_map(|child| match child {
+            RTreeNode::DirectoryNode(ref data) if data.id == id => Some(data),
+            _ => None,
+        })
+    }
+
+    /// Returns the child directory node with a given id.
+    fn child_node_mut(&mut self, id: NodeId) -> Option<&mut DirectoryNodeData<T>> {
+        self.children.iter_mut().find_map(|child| match child {
+            RTreeNode::DirectoryNode(ref mut data) if data.id == id => Some(data),
+            _ => None,
+        })
+    }
+
+    /// Removes the first object matched by `matches` from the node reached by following
+    /// the ids in `path`, starting with a child of this node.
+    ///
+    /// Nodes on the path that become underfull are dissolved, their remaining children
+    /// are appended to `orphans` and must be reinserted.
+    fn remove_at_path<M, O>(
+        &mut self,
+        path: &[NodeId],
+        matches: &mut M,
+        orphans: &mut Vec<RTreeNode<T>>,
+        observer: &mut O,
+    ) -> Option<T>
+    where
+        M: FnMut(&T) -> bool,
+        O: NodeObserver<T>,
+    {
+        let result = match path.split_first() {
+            None => {
+                let index = self.children.iter().position(|child| match child {
+                    RTreeNode::Leaf(ref t) => matches(t),
+                    RTreeNode::DirectoryNode(_) => false,
+                })?;
+                match self.children.swap_remove(index) {
+                    RTreeNode::Leaf(t) => t,
+                    RTreeNode::DirectoryNode(_) => unreachable!(),
+                }
+            }
+            Some((id, path)) => {
+                let index = self.children.iter().position(|child| match child {
+                    RTreeNode::DirectoryNode(ref data) => data.id == *id,
+                    RTreeNode::Leaf(_) => false,
+                })?;
+                let (result, underfull) = match self.children[index] {
+                    RTreeNode::DirectoryNode(ref mut data) => {
+                        let result = data.remove_at_path(path, matches, orphans, observer)?;
+                        (result, data.children.len() < self.options.min_size)
+                    }
+                    RTreeNode::Leaf(_) => unreachable!(),
+                };
+                if underfull {
+                    if let RTreeNode::DirectoryNode(data) = self.children.swap_remove(index) {
+                        observer.dropped(data.id);
+                        orphans.extend(data.children);
+                    }
+                }
+                result
+            }
+        };
+        self.update_mbr();
+        Some(result)
+    }
+
+    fn lookup_and_remove(&mut self, point: &T::Point) -> Option<T> {
+        let contains = self
+            .bounding_box
+            .as_ref()
+            .map(|bb| bb.contains_point(point))
+            .unwrap_or(false);
+        if contains {
+            let mut children = ::std::mem::replace(&mut self.children, Vec::new());
+            let mut result = None;
+            for child in children.drain(..) {
+                match child {
+                    RTreeNode::DirectoryNode(mut data) => {
+                        result = data.lookup_and_remove(point).or(result);
+                        if !data.children.is_empty() {
+                            // Don't add a node if it has become empty
+                            self.children.push(RTreeNode::DirectoryNode(data));
+                        }
+                    }
+                    RTreeNode::Leaf(b) => {
+                        if b.contains(point) {
+                            result = Some(b);
+                        } else {
+                            self.children.push(RTreeNode::Leaf(b))
+                        }
+                    }
+                }
+            }
+            if result.is_some() {
+                // Update the mbr if we did remove an element
+                self.update_mbr();
+            }
+            result
+        } else {
+            None
+        }
+    }
+}
+
+#[doc(hidden)]
+impl<T> DirectoryNodeData<T>
+where
+    T: SpatialObject + PartialEq,
+{
+    pub fn remove(&mut self, to_remove: &T) -> bool {
+        let contains = self
+            .bounding_box
+            .as_ref()
+            .map(|bb| bb.contains_rect(&to_remove.mbr()))
+            .unwrap_or(false);
+        if contains {
+            let mut result = false;
+            let mut remove_index = None;
+            for (index, child) in self.children.iter_mut().enumerate() {
+                match child {
+                    RTreeNode::DirectoryNode(ref mut data) => {
+                        if data.remove(to_remove) {
+                            result = true;
+                            if data.children.is_empty() {
+                                // Mark this child for removal as it has become empty
+                                remove_index = Some(index);
+                            }
+                            break;
+                        }
+                    }
+                    RTreeNode::Leaf(ref t) => {
+                        if t == to_remove {
+                            remove_index = Some(index);
+                            result = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            if let Some(to_remove) = remove_index {
+                self.children.remove(to_remove);
+                self.update_mbr();
+            } else if result {
+                self.update_aggregate();
+            }
+            result
+        } else {
+            false
+        }
+    }
+
+    fn contains(&self, obj: &T) -> bool {
+        let contains = self
+            .bounding_box
+            .as_ref()
+            .map(|bb| bb.contains_rect(&obj.mbr()))
+            .unwrap_or(false);
+        if contains {
+            for child in self.children.iter() {
+                match child {
+                    RTreeNode::DirectoryNode(ref data) => {
+                        if data.contains(obj) {
+                            return true;
+                        }
+                    }
+                    RTreeNode::Leaf(ref t) => {
+                        if t == obj {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn update(&mut self, old: &T, new: T, old_mbr: &BoundingRect<T::Point>) -> UpdateResult<T> {
+        let contains = self
+            .bounding_box
+            .as_ref()
+            .map(|bb| bb.contains_rect(old_mbr))
+            .unwrap_or(false);
+        if !contains {
+            return UpdateResult::NotFound(new);
+        }
+        if self.depth == 1 {
+            let index = self.children.iter().position(|child| match child {
+                RTreeNode::Leaf(ref t) => t == old,
+                RTreeNode::DirectoryNode(_) => false,
+            });
+            let index = match index {
+                Some(index) => index,
+                None => return UpdateResult::NotFound(new),
+            };
+            let fits = self.mbr().contains_rect(&new.mbr());
+            let result = if fits {
+                // Modify in place, the node's mbr can only shrink
+                self.children[index] = RTreeNode::Leaf(new);
+                UpdateResult::Updated
+            } else {
+                self.children.swap_remove(index);
+                UpdateResult::Reinsert(new)
+            };
+            self.update_mbr();
+            return result;
+        }
+        let mut new = new;
+        for index in 0..self.children.len() {
+            if let RTreeNode::DirectoryNode(ref mut data) = self.children[index] {
+                new = match data.update(old, new, old_mbr) {
+                    UpdateResult::NotFound(new) => new,
+                    result => {
+                        if data.children.is_empty() {
+                            self.children.swap_remove(index);
+                        }
+                        // Tighten this node's mbr as the old object may have been on its border
+                        self.update_mbr();
+                        return result;
+                    }
+                }
+            }
+        }
+        UpdateResult::NotFound(new)
+    }
+}
+
+enum UpdateResult<T> {
+    NotFound(T),
+    Updated,
+    Reinsert(T),
+}
+
+enum InsertionResult<T>
+where
+    T: SpatialObject,
+{
+    Complete,
+    Split(RTreeNode<T>),
+    Reinsert(Vec<RTreeNode<T>>),
+}
+
+struct InsertionState {
+    reinsertions: Vec<bool>,
+}
+
+impl InsertionState {
+    fn new(max_depth: usize) -> InsertionState {
+        let mut reinsertions = Vec::with_capacity(max_depth + 1);
+        reinsertions.resize(max_depth, false);
+        InsertionState { reinsertions }
+    }
+
+    fn did_reinsert(&self, depth: usize) -> bool {
+        self.reinsertions[depth]
+    }
+
+    fn mark_reinsertion(&mut self, depth: usize) {
+        self.reinsertions[depth] = true;
+    }
+}
+
+#[doc(hidden)]
+impl<T> RTreeNode<T>
+where
+    T: SpatialObject,
+{
+    pub fn depth(&self) -> usize {
+        match self {
+            RTreeNode::DirectoryNode(ref data) => data.depth,
+            _ => 0,
+        }
+    }
+
+    pub fn mbr(&self) -> BoundingRect<T::Point> {
+        match self {
+            RTreeNode::DirectoryNode(ref data) => data.bounding_box.clone().unwrap(),
+            RTreeNode::Leaf(ref t) => t.mbr(),
+        }
+    }
+
+    fn nearest_neighbors<'a>(
+        &'a self,
+        point: &T::Point,
+        nearest_distance: Option<<T::Point as PointN>::Scalar>,
+        result: &mut Vec<&'a T>,
+    ) -> Option<<T::Point as PointN>::Scalar> {
+        match self {
+            RTreeNode::DirectoryNode(ref data) => {
+                data.nearest_neighbors(point, nearest_distance, result)
+            }
+            RTreeNode::Leaf(ref t) => {
+                let distance = t.distance2(point);
+                match nearest_distance {
+                    Some(nearest) => {
+                        if distance <= nearest {
+                            if distance < nearest {
+                                // We've found a new minimum element, remove all other neighbors found so far
+                                result.clear();
+                            }
+                            result.push(t);
+                            Some(distance)
+                        } else {
+                            // This object is not among the nearest neigbors
+                            None
+                        }
+                    }
+                    None => {
+                        result.push(t);
+                        Some(distance)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_serialize",
+    serde(bound(
+        serialize = "T: Serialize, T::Point: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Point: Deserialize<'de>"
+    ))
+)]
+pub struct DirectoryNodeData<T>
+where
+    T: SpatialObject,
+{
+    bounding_box: Option<BoundingRect<T::Point>>,
+    children: Vec<RTreeNode<T>>,
+    depth: usize,
+    options: Arc<RTreeOptions>,
+    #[cfg_attr(feature = "serde_serialize", serde(skip))]
+    aggregate: Option<NodeAggregate<T>>,
+    #[cfg_attr(feature = "serde_serialize", serde(skip, default = "NodeId::new"))]
+    id: NodeId,
+}
+
+/// Identifies a directory node.
+///
+/// Each created node receives a new id which stays the same while the node is moved
+/// within its tree. Only clones of a node share its id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct NodeId(usize);
+
+impl NodeId {
+    fn new() -> NodeId {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        NodeId(NEXT_ID.fetch_add(1, AtomicOrdering::Relaxed))
+    }
+}
+
+/// Observes how entries are moved between the directory nodes of a tree.
+///
+/// Used by `HandleRTree` to keep its index from handles to leaf nodes up to date.
+trait NodeObserver<T>
+where
+    T: SpatialObject,
+{
+    /// Called after `child` has been added to the directory node `parent`.
+    fn added(&mut self, child: &RTreeNode<T>, parent: NodeId);
+
+    /// Called after a directory node has been removed from the tree.
+    fn dropped(&mut self, node: NodeId);
+}
+
+/// Ignores all changes, used by plain `RTree`s.
+struct NoObserver;
+
+impl<T> NodeObserver<T> for NoObserver
+where
+    T: SpatialObject,
+{
+    fn added(&mut self, _: &RTreeNode<T>, _: NodeId) {}
+
+    fn dropped(&mut self, _: NodeId) {}
+}
+
+#[doc(hidden)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_serialize",
+    serde(bound(
+        serialize = "T: Serialize, T::Point: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Point: Deserialize<'de>"
+    ))
+)]
+pub enum RTreeNode<T>
+where
+    T: SpatialObject,
+{
+    Leaf(T),
+    DirectoryNode(DirectoryNodeData<T>),
+}
+
+/// A rust implementation of n dimensional r*-trees
+///
+/// [R-trees](https://en.wikipedia.org/wiki/R-tree) provide efficient nearest-neighbor searches for
+/// many objects. [R*-trees](https://en.wikipedia.org/wiki/R*_tree) (&quot;R-Star-Trees&quot;)
+/// are a common variant of r-trees and use more advanced heuristics to improve query performance.
+/// Instead of linear time complexity, r-trees yield logarithmic complexity for look-up operations
+/// and nearest neighbor queries. Inserting into an r-tree runs in O(log(n)) time on average.
+/// Also, a bulk loading algorithm is implemented for faster and higher quality tree creation.
+/// Some simple geometric primitives that can be inserted into an r-tree can be found in
+/// the `primitives` module. If your object is not among those, consider implementing the
+/// `SpatialObject` trait.
+///
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate spade;
+///
+/// use nalgebra::{Point4};
+/// use spade::rtree::RTree;
+///
+/// # fn main() {
+///   let mut tree = RTree::new();
+///   tree.insert(Point4::new(13i32, 10, 10, 37));
+/// # }
+/// ```
+/// # Basic Example
+///
+/// ```
+/// extern crate cgmath; // Alternatively: use nalgebra or [f32; 2]
+/// extern crate spade;
+///
+/// use spade::rtree::RTree;
+/// use cgmath::Point2;
+///
+/// fn main() {
+/// let mut rtree = RTree::new();
+/// // Insert two points
+/// rtree.insert(Point2::new(0.5, 0.5f32));
+/// rtree.insert(Point2::new(1.0, 1.0f32));
+///
+/// if rtree.lookup(&Point2::new(0.5, 0.5)).is_some() {
+///   println!("Found point at [0.5, 0.5]/");
+/// }
+///
+/// let nearest = rtree.nearest_neighbor(&Point2::new(1.5, 1.5)).unwrap();
+/// println!("nearest neighbor at [1.5, 1.5]: {:?}", nearest);
+///
+/// // Iterate over all elements
+/// for point in rtree.iter() {
+///   println!("Found point: {:?}", point);
+/// }
+/// }
+/// ```
+#[derive(Clone)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_serialize",
+    serde(bound(
+        serialize = "T: Serialize, T::Point: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Point: Deserialize<'de>"
+    ))
+)]
+pub struct RTree<T>
+where
+    T: SpatialObject,
+{
+    root: DirectoryNodeData<T>,
+    size: usize,
+}
+
+impl<T> ::std::fmt::Debug for RTree<T>
+where
+    T: SpatialObject + ::std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        let mut iter = self.iter();
+        write!(f, "RTree {{")?;
+        if let Some(next) = iter.next() {
+            next.fmt(f)?
+        }
+        for t in iter {
+            write!(f, ", {:?}", t)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl<T> Default for RTree<T>
+where
+    T: SpatialObject,
+{
+    fn default() -> RTree<T> {
+        RTree::new()
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject,
+{
+    /// Creates an empty r*-tree.
+    pub fn new() -> RTree<T> {
+        RTree::new_with_options(Default::default())
+    }
+
+    /// Returns the trees minimal bounding box.
+    pub fn mbr(&self) -> Option<BoundingRect<T::Point>> {
+        self.root.bounding_box.clone()
+    }
+
+    /// Creates an empty r*-tree with custom options.
+    pub fn new_with_options(options: RTreeOptions) -> RTree<T> {
+        let options = Arc::new(options);
+        RTree {
+            root: DirectoryNodeData::new(1, options),
+            size: 0,
+        }
+    }
+
+    /// Returns the number of elements contained in this r-tree.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns an iterator over all contained elements.
+    pub fn iter(&self) -> RTreeIterator<T> {
+        RTreeIterator::new(&self.root)
+    }
+
+    /// Returns a mutable iterator over all contained elements.
+    ///
+    /// *Do not change the objects' minimal bounding boxes*, see `RTreeObjectMut`.
+    pub fn iter_mut(&mut self) -> SelectionIteratorMut<'_, T, SelectAll> {
+        self.locate_with_selection_function_mut(SelectAll)
+    }
+
+    /// Returns a read-only reference to the tree's root node.
+    ///
+    /// The returned node can be used to traverse the tree's structure, see
+    /// `DirectoryNodeRef`. Returns `None` if the tree is empty.
+    pub fn root_node(&self) -> Option<DirectoryNodeRef<'_, T>> {
+        if self.root.children.is_empty() {
+            None
+        } else {
+            Some(DirectoryNodeRef::new(&self.root))
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn root(&self) -> &DirectoryNodeData<T> {
+        // This access is only needed for one of the examples
+        &self.root
+    }
+
+    /// Returns the nearest neighbor.
+    ///
+    /// Returns `None` if the tree is empty.
+    pub fn nearest_neighbor(&self, query_point: &T::Point) -> Option<&T> {
+        let result = self.root.nearest_neighbor(query_point);
+        if result.is_none() && self.size > 0 {
+            // In some cases, the nearest element can be pruned by its own MinMax distance
+            // Use the iterator for these cases (which doesn't prune)
+            self.nearest_neighbor_iterator(query_point).next()
+        } else {
+            result
+        }
+    }
+
+    /// Returns a mutable reference to the nearest neighbor.
+    ///
+    /// Returns `None` if the tree is empty. If multiple objects have the same distance,
+    /// one of them will be returned.
+    /// *Do not change the object's minimal bounding box*, see `RTreeObjectMut`.
+    pub fn nearest_neighbor_mut(
+        &mut self,
+        query_point: &T::Point,
+    ) -> Option<RTreeObjectMut<'_, T>> {
+        let mut heap = ::std::collections::binary_heap::BinaryHeap::new();
+        let mut children: &mut [RTreeNode<T>] = &mut self.root.children;
+        loop {
+            for child in children {
+                let distance = match child {
+                    RTreeNode::DirectoryNode(ref data) => data.mbr().min_dist2(query_point),
+                    RTreeNode::Leaf(ref t) => t.distance2(query_point),
+                };
+                heap.push(DistanceWrapper {
+                    node: child,
+                    distance,
+                });
+            }
+            match heap.pop()?.node {
+                RTreeNode::DirectoryNode(data) => children = &mut data.children,
+                RTreeNode::Leaf(t) => return Some(RTreeObjectMut::new(t)),
+            }
+        }
+    }
+
+    /// Returns an object close to a given point. This operation is faster than
+    /// `nearest_neighbor` but will not neccessarily yield the real nearest neighbor.
+    pub fn close_neighbor(&self, point: &T::Point) -> Option<&T> {
+        self.root.close_neighbor(point)
+    }
+
+    /// Returns the nearest neighbors of a given point.
+    ///
+    /// All returned values will have the exact same distance from the given query point.
+    /// Returns an empty `Vec` if the tree is empty.
+    pub fn nearest_neighbors(&self, query_point: &T::Point) -> Vec<&T> {
+        let mut result = Vec::new();
+        if self.size > 0 {
+            self.root.nearest_neighbors(query_point, None, &mut result);
+        }
+        result
+    }
+
+    /// Returns the nearest n neighbors.
+    pub fn nearest_n_neighbors(&self, query_point: &T::Point, n: usize) -> Vec<&T> {
+        self.nearest_neighbor_iterator(query_point)
+            .take(n)
+            .collect()
+    }
+
+    /// Returns an iterator over the nearest neighbors of a point.
+    pub fn nearest_neighbor_iterator(&self, query_point: &T::Point) -> NearestNeighborIterator<T> {
+        NearestNeighborIterator::new(&self.root, query_point.clone(), Euclidean)
+    }
+
+    /// Returns the nearest neighbor with respect to a given metric.
+    ///
+    /// Returns `None` if the tree is empty.
+    pub fn nearest_neighbor_with_metric<M>(&self, query_point: &T::Point, metric: M) -> Option<&T>
+    where
+        M: Metric<T>,
+    {
+        self.nearest_neighbor_iterator_with_metric(query_point, metric)
+            .next()
+    }
+
+    /// Returns an iterator over the nearest neighbors of a point with respect to a
+    /// given metric.
+    pub fn nearest_neighbor_iterator_with_metric<M>(
+        &self,
+        query_point: &T::Point,
+        metric: M,
+    ) -> NearestNeighborIterator<'_, T, M>
+    where
+        M: Metric<T>,
+    {
+        NearestNeighborIterator::new(&self.root, query_point.clone(), metric)
+    }
+
+    /// Returns an iterator over the nearest neighbors of a point and their squared distance
+    /// to it.
+    ///
+    /// The distance is the one already computed during the search, there is no need to
+    /// call `distance2` again.
+    pub fn nearest_neighbor_iter_with_distance2(
+        &self,
+        query_point: &T::Point,
+    ) -> NearestNeighborDistance2Iterator<'_, T> {
+        NearestNeighborDistance2Iterator::new(&self.root, query_point.clone(), None, Euclidean)
+    }
+
+    /// Returns the nearest n neighbors that are not further away than a given distance.
+    ///
+    /// Note that `max_distance2` is the squared distance. Subtrees that are further away
+    /// are pruned as a whole. The result is sorted by distance and contains fewer than
+    /// `n` elements if there are not enough objects within `max_distance2`.
+    pub fn nearest_n_neighbors_within(
+        &self,
+        query_point: &T::Point,
+        n: usize,
+        max_distance2: <T::Point as PointN>::Scalar,
+    ) -> Vec<&T> {
+        NearestNeighborDistance2Iterator::new(
+            &self.root,
+            query_point.clone(),
+            Some(max_distance2),
+            Euclidean,
+        )
+        .map(|(t, _)| t)
+        .take(n)
+        .collect()
+    }
+
+    /// Returns the nearest neighbor for which a predicate holds.
+    ///
+    /// Objects rejected by `predicate` are never added to the search queue.
+    /// Returns `None` if no object satisfies the predicate.
+    pub fn nearest_neighbor_where<F>(&self, query_point: &T::Point, mut predicate: F) -> Option<&T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut heap = ::std::collections::binary_heap::BinaryHeap::new();
+        let mut children: &[RTreeNode<T>] = &self.root.children;
+        loop {
+            for child in children {
+                let distance = match child {
+                    RTreeNode::DirectoryNode(ref data) => data.mbr().min_dist2(query_point),
+                    RTreeNode::Leaf(ref t) if predicate(t) => t.distance2(query_point),
+                    RTreeNode::Leaf(_) => continue,
+                };
+                heap.push(RTreeNodeDistanceWrapper {
+                    node: child,
+                    distance,
+                });
+            }
+            match heap.pop()?.node {
+                RTreeNode::DirectoryNode(ref data) => children = &data.children,
+                RTreeNode::Leaf(ref t) => return Some(t),
+            }
+        }
+    }
+
+    /// Returns all objects (partially) contained in a rectangle
+    pub fn lookup_in_rectangle(&self, query_rect: &BoundingRect<T::Point>) -> Vec<&T> {
+        let mut result = Vec::new();
+        self.lookup_in_rectangle_to(&mut result, query_rect);
+        result
+    }
+    /// Writes all objects (partially) contained in a rectangle. Does not clear the vector.
+    pub fn lookup_in_rectangle_to<'a>(
+        &'a self,
+        result: &mut Vec<&'a T>,
+        query_rect: &BoundingRect<T::Point>,
+    ) {
+        result.extend(self.lookup_in_rectangle_iter(query_rect));
+    }
+
+    /// Returns an iterator over all objects (partially) contained in a rectangle.
+    ///
+    /// Unlike `lookup_in_rectangle`, the tree is traversed lazily and no memory is
+    /// allocated per found object. Use this if only a few results or just the number
+    /// of results are needed.
+    pub fn lookup_in_rectangle_iter(
+        &self,
+        query_rect: &BoundingRect<T::Point>,
+    ) -> SelectionIterator<'_, T, SelectInRectangle<T::Point>> {
+        self.locate_with_selection_function(SelectInRectangle::new(query_rect.clone()))
+    }
+
+    /// Returns a mutable iterator over all objects (partially) contained in a rectangle.
+    ///
+    /// *Do not change the objects' minimal bounding boxes*, see `RTreeObjectMut`.
+    pub fn lookup_in_rectangle_mut(
+        &mut self,
+        query_rect: &BoundingRect<T::Point>,
+    ) -> SelectionIteratorMut<'_, T, SelectInRectangle<T::Point>> {
+        self.locate_with_selection_function_mut(SelectInRectangle::new(query_rect.clone()))
+    }
+
+    /// Returns all objects (partially) contained in a circle.
+    ///
+    /// Note that `radius2` is the circle's squared radius, not the actual radius.
+    /// An object is contained if a part of it lies within the circle.
+    pub fn lookup_in_circle(
+        &self,
+        circle_origin: &T::Point,
+        radius2: &<T::Point as PointN>::Scalar,
+    ) -> Vec<&T> {
+        let mut result = Vec::new();
+        self.lookup_in_circle_to(&mut result, circle_origin, radius2);
+        result
+    }
+    /// Writes all objects (partially) contained in a circle. Does not clear the vector.
+    ///
+    /// Note that `radius2` is the circle's squared radius, not the actual radius.
+    /// An object is contained if a part of it lies within the circle.
+    pub fn lookup_in_circle_to<'a>(
+        &'a self,
+        result: &mut Vec<&'a T>,
+        circle_origin: &T::Point,
+        radius2: &<T::Point as PointN>::Scalar,
+    ) {
+        result.extend(self.lookup_in_circle_iter(circle_origin, radius2));
+    }
+
+    /// Returns an iterator over all objects (partially) contained in a circle.
+    ///
+    /// Note that `radius2` is the circle's squared radius, not the actual radius.
+    /// Unlike `lookup_in_circle`, the tree is traversed lazily and no memory is
+    /// allocated per found object.
+    pub fn lookup_in_circle_iter(
+        &self,
+        circle_origin: &T::Point,
+        radius2: &<T::Point as PointN>::Scalar,
+    ) -> SelectionIterator<'_, T, SelectInCircle<T::Point>> {
+        self.locate_with_selection_function(SelectInCircle::new(
+            circle_origin.clone(),
+            radius2.clone(),
+        ))
+    }
+
+    /// Returns all objects within a given distance of a point, measured with a `Metric`.
+    ///
+    /// `max_distance` uses the metric's units, e.g. it is the squared distance for
+    /// `Euclidean`, making this method equivalent to `lookup_in_circle`.
+    /// For `Manhattan` or `Chebyshev`, the "circle" is a diamond or square.
+    pub fn lookup_in_circle_with_metric<M>(
+        &self,
+        circle_origin: &T::Point,
+        max_distance: &<T::Point as PointN>::Scalar,
+        metric: M,
+    ) -> Vec<&T>
+    where
+        M: Metric<T>,
+    {
+        self.locate_with_selection_function(SelectWithinDistance::new(
+            circle_origin.clone(),
+            max_distance.clone(),
+            metric,
+        ))
+        .collect()
+    }
+
+    /// Returns a mutable iterator over all objects (partially) contained in a circle.
+    ///
+    /// Note that `radius2` is the circle's squared radius, not the actual radius.
+    /// *Do not change the objects' minimal bounding boxes*, see `RTreeObjectMut`.
+    pub fn lookup_in_circle_mut(
+        &mut self,
+        circle_origin: &T::Point,
+        radius2: &<T::Point as PointN>::Scalar,
+    ) -> SelectionIteratorMut<'_, T, SelectInCircle<T::Point>> {
+        self.locate_with_selection_function_mut(SelectInCircle::new(
+            circle_origin.clone(),
+            radius2.clone(),
+        ))
+    }
+
+    /// Returns an iterator over all objects selected by a selection function.
+    ///
+    /// The tree is traversed lazily. Only subtrees for which `function.should_descend` returns
+    /// `true` are searched, and only objects for which `function.matches` returns `true` are
+    /// yielded. See `SelectionFunction` for an example.
+    pub fn locate_with_selection_function<F>(&self, function: F) -> SelectionIterator<'_, T, F>
+    where
+        F: SelectionFunction<T>,
+    {
+        SelectionIterator::new(&self.root, function)
+    }
+
+    /// Returns a mutable iterator over all objects selected by a selection function.
+    ///
+    /// *Do not change the objects' minimal bounding boxes*, see `RTreeObjectMut`.
+    pub fn locate_with_selection_function_mut<F>(
+        &mut self,
+        function: F,
+    ) -> SelectionIteratorMut<'_, T, F>
+    where
+        F: SelectionFunction<T>,
+    {
+        SelectionIteratorMut::new(&mut self.root, function)
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject,
+{
+    /// Returns all pairs of objects from this and another tree whose minimal bounding
+    /// rectangles intersect.
+    ///
+    /// Both trees are traversed simultaneously, which is considerably faster than
+    /// looking up each element of one tree in the other. Use `SpatialJoinIterator::refine`
+    /// to filter the candidates with an exact intersection test.
+    pub fn spatial_join<'a, U>(&'a self, other: &'a RTree<U>) -> SpatialJoinIterator<'a, T, U>
+    where
+        U: SpatialObject<Point = T::Point>,
+    {
+        SpatialJoinIterator::new(&self.root, &other.root, zero())
+    }
+
+    /// Returns all pairs of objects from this and another tree whose minimal bounding
+    /// rectangles are not further apart than a given distance.
+    ///
+    /// Note that `max_distance2` is the squared distance, not the actual distance.
+    pub fn spatial_join_within_distance<'a, U>(
+        &'a self,
+        other: &'a RTree<U>,
+        max_distance2: <T::Point as PointN>::Scalar,
+    ) -> SpatialJoinIterator<'a, T, U>
+    where
+        U: SpatialObject<Point = T::Point>,
+    {
+        SpatialJoinIterator::new(&self.root, &other.root, max_distance2)
+    }
+
+    /// Returns all pairs of objects from this and another tree, ordered by their distance.
+    ///
+    /// The pairs are computed lazily, taking the first `k` items of the iterator yields the
+    /// `k` closest pairs. See `ClosestPairIterator` for how distances are measured.
+    pub fn closest_pair_iterator<'a, U>(
+        &'a self,
+        other: &'a RTree<U>,
+    ) -> ClosestPairIterator<'a, T, U>
+    where
+        U: SpatialObject<Point = T::Point>,
+    {
+        ClosestPairIterator::new(&self.root, &other.root)
+    }
+
+    /// Returns the `k` closest pairs of objects from this and another tree.
+    ///
+    /// The pairs are sorted by their squared distance, which is returned as third element.
+    pub fn closest_pairs<'a, U>(
+        &'a self,
+        other: &'a RTree<U>,
+        k: usize,
+    ) -> Vec<(&'a T, &'a U, <T::Point as PointN>::Scalar)>
+    where
+        U: SpatialObject<Point = T::Point>,
+    {
+        self.closest_pair_iterator(other).take(k).collect()
+    }
+
+    /// Returns the closest pair of objects from this and another tree.
+    ///
+    /// Returns `None` if any of both trees is empty.
+    pub fn closest_pair<'a, U>(
+        &'a self,
+        other: &'a RTree<U>,
+    ) -> Option<(&'a T, &'a U, <T::Point as PointN>::Scalar)>
+    where
+        U: SpatialObject<Point = T::Point>,
+    {
+        self.closest_pair_iterator(other).next()
+    }
+
+    /// Returns all pairs of distinct objects from this tree, ordered by their distance.
+    ///
+    /// Each pair is yielded only once, an object is never paired with itself. Equal
+    /// objects that were inserted multiple times are still paired with distance zero.
+    pub fn self_closest_pair_iterator(&self) -> ClosestPairIterator<'_, T, T> {
+        ClosestPairIterator::new_self_join(&self.root)
+    }
+
+    /// Returns the `k` closest pairs of distinct objects from this tree.
+    pub fn self_closest_pairs(&self, k: usize) -> Vec<(&T, &T, <T::Point as PointN>::Scalar)> {
+        self.self_closest_pair_iterator().take(k).collect()
+    }
+
+    /// Returns the closest pair of distinct objects from this tree.
+    ///
+    /// Returns `None` if the tree contains less than two objects.
+    pub fn self_closest_pair(&self) -> Option<(&T, &T, <T::Point as PointN>::Scalar)> {
+        self.self_closest_pair_iterator().next()
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject + Clone,
+{
+    /// Creates a new rtree with some initial elements.
+    ///
+    /// This method should run faster than inserting all elements sequentially.
+    /// Also, the resulting rtree should have better quality in terms of
+    /// query performance. This is an implementation of the
+    /// [OMT algorithm](http://ftp.informatik.rwth-aachen.de/Publications/CEUR-WS/Vol-74/files/FORUM_18.pdf),
+    /// generalized to any number of dimensions by partitioning the elements along all axes.
+    pub fn bulk_load(elements: Vec<T>) -> RTree<T> {
+        Self::bulk_load_with_options(Default::default(), elements)
+    }
+
+    /// Creates a new rtree with some initial elements and custom options.
+    ///
+    /// The bulk loaded nodes contain up to `bulk_load_size` children, see
+    /// `RTreeOptions::bulk_load_size`.
+    pub fn bulk_load_with_options(options: RTreeOptions, mut elements: Vec<T>) -> RTree<T> {
+        let options = Arc::new(options);
+        RTree {
+            root: DirectoryNodeData::bulk_load(options, &mut elements),
+            size: elements.len(),
+        }
+    }
+
+    /// Inserts many elements at once.
+    ///
+    /// The elements are bulk loaded into subtrees which are then grafted into the
+    /// existing tree at the appropriate level, see `merge`. This is considerably
+    /// faster than inserting each element on its own.
+    pub fn bulk_insert(&mut self, mut elements: Vec<T>) {
+        if elements.is_empty() {
+            return;
+        }
+        let options = self.root.options.clone();
+        let batch = RTree {
+            root: DirectoryNodeData::bulk_load(options, &mut elements),
+            size: elements.len(),
+        };
+        self.merge(batch);
+    }
+}
+
+impl<T> Extend<T> for RTree<T>
+where
+    T: SpatialObject + Clone,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.bulk_insert(iter.into_iter().collect());
+    }
+}
+
+impl<T> ::std::iter::FromIterator<T> for RTree<T>
+where
+    T: SpatialObject + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        RTree::bulk_load(iter.into_iter().collect())
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject,
+{
+    /// Searches for an element at a given position.
+    ///
+    /// If `query_point` is contained by one object in the tree, this object will be returned.
+    /// If multiple objects contain the point, only one of them will be returned.
+    pub fn lookup(&self, query_point: &T::Point) -> Option<&T> {
+        self.locate_with_selection_function(SelectAtPoint::new(query_point.clone()))
+            .next()
+    }
+
+    /// Searches for an element at a given position and returns a mutable
+    /// reference.
+    /// If `query_point` is contained by multiple objects in the tree,
+    /// one of them will be returned.
+    /// *Do not change the object's minimal bounding box*.
+    pub fn lookup_mut(&mut self, query_point: &T::Point) -> Option<&mut T> {
+        self.locate_with_selection_function_mut(SelectAtPoint::new(query_point.clone()))
+            .next_object()
+    }
+
+    /// Inserts a new element into the tree.
+    ///
+    /// This will require `O(log(n))` operations on average, where n is the number of
+    /// elements contained in the tree.
+    pub fn insert(&mut self, t: T) {
+        self.insert_node(RTreeNode::Leaf(t));
+        self.size += 1;
+    }
+
+    fn insert_node(&mut self, node: RTreeNode<T>) {
+        self.insert_observed_node(node, &mut NoObserver);
+    }
+
+    fn insert_observed_node<O>(&mut self, node: RTreeNode<T>, observer: &mut O)
+    where
+        O: NodeObserver<T>,
+    {
+        let mut state = InsertionState::new(self.root.depth + 1);
+        let mut insertion_stack = vec![node];
+        while let Some(next) = insertion_stack.pop() {
+            match self.root.insert(next, &mut state, observer) {
+                InsertionResult::Split(node) => {
+                    // The root node was split, create a new root and increase depth
+                    let new_depth = self.root.depth + 1;
+                    let options = self.root.options.clone();
+                    let old_root = ::std::mem::replace(
+                        &mut self.root,
+                        DirectoryNodeData::new(new_depth, options),
+                    );
+                    let aggregator = old_root.aggregator();
+                    self.root
+                        .add_children(vec![RTreeNode::DirectoryNode(old_root), node]);
+                    self.root.set_node_aggregator(aggregator);
+                    for child in &self.root.children {
+                        observer.added(child, self.root.id);
+                    }
+                }
+                InsertionResult::Reinsert(nodes) => {
+                    // Schedule elements for reinsertion
+                    insertion_stack.extend(nodes);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Merges another tree into this tree.
+    ///
+    /// Instead of reinserting every object, `other`'s subtrees are grafted into this
+    /// tree at the level matching their height, splitting nodes where necessary. If
+    /// `other` is higher than this tree, the roles of both trees are swapped. Trees
+    /// created with different options are merged by inserting each object.
+    pub fn merge(&mut self, mut other: RTree<T>) {
+        if other.size == 0 {
+            return;
+        }
+        if !other
+            .root
+            .options
+            .has_same_insertion_parameters(&self.root.options)
+        {
+            let mut leaves = Vec::with_capacity(other.size);
+            other.root.into_leaves(&mut leaves);
+            for leaf in leaves {
+                self.insert(leaf);
+            }
+            return;
+        }
+        if other.root.depth > self.root.depth || self.size == 0 {
+            let aggregator = self.root.aggregator();
+            let options = self.root.options.clone();
+            ::std::mem::swap(&mut self.root, &mut other.root);
+            // Inner nodes only use the insertion parameters, which are equal. Keep this
+            // tree's bulk load size for later calls to `bulk_insert`.
+            self.root.options = options;
+            ::std::mem::swap(&mut self.size, &mut other.size);
+            if aggregator.is_some() || self.root.aggregate.is_some() {
+                self.root.set_aggregator(aggregator);
+            }
+            if other.size == 0 {
+                return;
+            }
+        }
+        self.size += other.size;
+        self.graft(other.root);
+    }
+
+    /// Inserts all entries of a subtree, e.g. of a bulk loaded tree.
+    ///
+    /// Subtrees lower than the root are inserted as a whole at their level, higher
+    /// subtrees are split up into their children first. Subtrees with fewer than
+    /// `min_size` children are split up as well, grafting them would leave underfull
+    /// nodes behind. Does not update the tree's size.
+    fn graft(&mut self, subtree: DirectoryNodeData<T>) {
+        let aggregator = self.root.aggregator();
+        let min_size = self.root.options.min_size;
+        let mut stack = vec![subtree];
+        while let Some(mut node) = stack.pop() {
+            if node.depth < self.root.depth && node.children.len() >= min_size {
+                if aggregator.is_some() || node.aggregate.is_some() {
+                    node.set_aggregator(aggregator.clone());
+                }
+                self.insert_node(RTreeNode::DirectoryNode(node));
+                continue;
+            }
+            for child in node.children {
+                match child {
+                    RTreeNode::DirectoryNode(data) => stack.push(data),
+                    leaf => self.insert_node(leaf),
+                }
+            }
+        }
+    }
+
+    /// Removes all objects selected by a selection function and returns them.
+    ///
+    /// All objects are removed within a single traversal of the tree. Nodes that
+    /// become underfull are dissolved and their remaining entries are reinserted
+    /// afterwards.
+    pub fn drain_with_selection_function<F>(&mut self, function: F) -> Vec<T>
+    where
+        F: SelectionFunction<T>,
+    {
+        self.drain_where(
+            &|mbr: &BoundingRect<T::Point>| function.should_descend(mbr),
+            &mut |t: &T| function.matches(t),
+        )
+    }
+
+    /// Removes all objects (partially) contained in a rectangle and returns them.
+    pub fn drain_in_rectangle(&mut self, query_rect: &BoundingRect<T::Point>) -> Vec<T> {
+        self.drain_with_selection_function(SelectInRectangle::new(query_rect.clone()))
+    }
+
+    /// Removes all objects (partially) contained in a circle and returns them.
+    ///
+    /// Note that `radius2` is the circle's squared radius, not the actual radius.
+    pub fn drain_in_circle(
+        &mut self,
+        circle_origin: &T::Point,
+        radius2: &<T::Point as PointN>::Scalar,
+    ) -> Vec<T> {
+        self.drain_with_selection_function(SelectInCircle::new(
+            circle_origin.clone(),
+            radius2.clone(),
+        ))
+    }
+
+    /// Retains only the objects specified by the predicate.
+    ///
+    /// Removes all objects for which `f` returns `false`. Like
+    /// `drain_with_selection_function`, this requires only one traversal of the tree.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.drain_where(&|_: &BoundingRect<T::Point>| true, &mut |t: &T| !f(t));
+    }
+
+    fn drain_where<D, M>(&mut self, should_descend: &D, matches: &mut M) -> Vec<T>
+    where
+        D: Fn(&BoundingRect<T::Point>) -> bool,
+        M: FnMut(&T) -> bool,
+    {
+        let mut removed = Vec::new();
+        let mut orphans = Vec::new();
+        if self.size == 0
+            || !self
+                .root
+                .drain_where(should_descend, matches, &mut removed, &mut orphans)
+        {
+            return removed;
+        }
+        self.size -= removed.len();
+        self.condense(orphans, &mut NoObserver);
+        removed
+    }
+
+    /// Removes the first object matched by `matches` from the leaf node reached by
+    /// following the ids in `path`, see `DirectoryNodeData::remove_at_path`.
+    fn remove_at_path<M, O>(
+        &mut self,
+        path: &[NodeId],
+        mut matches: M,
+        observer: &mut O,
+    ) -> Option<T>
+    where
+        M: FnMut(&T) -> bool,
+        O: NodeObserver<T>,
+    {
+        let mut orphans = Vec::new();
+        let result = self
+            .root
+            .remove_at_path(path, &mut matches, &mut orphans, observer)?;
+        self.size -= 1;
+        self.condense(orphans, observer);
+        Some(result)
+    }
+
+    /// Shrinks the tree after objects have been removed and reinserts the entries of all
+    /// dissolved nodes.
+    fn condense<O>(&mut self, mut orphans: Vec<RTreeNode<T>>, observer: &mut O)
+    where
+        O: NodeObserver<T>,
+    {
+        // Decrease the tree's height if the root has only one child left
+        while self.root.children.len() == 1 && self.root.depth > 1 {
+            if let Some(RTreeNode::DirectoryNode(child)) = self.root.children.pop() {
+                observer.dropped(self.root.id);
+                self.root = child;
+            }
+        }
+        if self.root.children.is_empty() {
+            // Use the largest orphan as new root
+            let deepest = orphans
+                .iter()
+                .enumerate()
+                .filter(|(_, orphan)| orphan.depth() > 0)
+                .max_by_key(|(_, orphan)| orphan.depth())
+                .map(|(index, _)| index);
+            self.root.depth = 1;
+            if let Some(index) = deepest {
+                if let RTreeNode::DirectoryNode(data) = orphans.swap_remove(index) {
+                    observer.dropped(self.root.id);
+                    self.root = data;
+                }
+            }
+        }
+
+        // Reinsert the entries of all dissolved nodes
+        while let Some(orphan) = orphans.pop() {
+            match orphan {
+                RTreeNode::DirectoryNode(data) if data.depth >= self.root.depth => {
+                    // This subtree is too high to be inserted
+                    observer.dropped(data.id);
+                    orphans.extend(data.children);
+                }
+                orphan => self.insert_observed_node(orphan, observer),
+            }
+        }
+    }
+
+    /// Searches for an element and removes it.
+    ///
+    /// If the given point is contained by one object in the tree, this object is being removed
+    /// and returned. If the point is contained by multiple objects, only one of them is removed and
+    /// returned.
+    pub fn lookup_and_remove(&mut self, query_point: &T::Point) -> Option<T> {
+        if self.size > 0 {
+            let result = self.root.lookup_and_remove(query_point);
+            if result.is_some() {
+                if self.root.children.is_empty() {
+                    self.root.depth = 1;
+                }
+                self.size -= 1;
+            }
+            result
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject + PartialEq,
+{
+    /// Removes an object from the tree.
+    ///
+    /// Locates and removes an object from the tree, returning
+    /// `true` if the element could be removed.
+    /// If multiple object's are equal to `to_remove`, only one
+    /// will be deleted.
+    pub fn remove(&mut self, obj: &T) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let result = self.root.remove(obj);
+        if self.root.children.is_empty() {
+            self.root.depth = 1;
+        }
+        if result {
+            self.size -= 1;
+        }
+        result
+    }
+
+    /// Returns `true` if a given object is contained in this tree.
+    pub fn contains(&self, obj: &T) -> bool {
+        self.root.contains(obj)
+    }
+
+    /// Replaces an object, e.g. to move it to a new position.
+    ///
+    /// If the new object's bounding box still fits into the directory node containing
+    /// `old`, the object is replaced in place and the bounding boxes of all its
+    /// ancestors are tightened. Otherwise, `old` is removed and `new` is inserted anew.
+    /// This is considerably faster than `remove` followed by `insert` for objects that
+    /// move only a small distance.
+    ///
+    /// Returns `true` if `old` was found. Otherwise, the tree remains unchanged and
+    /// `new` is dropped. If multiple objects are equal to `old`, only one is replaced.
+    pub fn update(&mut self, old: &T, new: T) -> bool {
+        match self.update_in_place(old, new) {
+            UpdateResult::NotFound(_) => false,
+            UpdateResult::Updated => true,
+            UpdateResult::Reinsert(new) => {
+                self.insert_node(RTreeNode::Leaf(new));
+                true
+            }
+        }
+    }
+
+    /// Replaces multiple objects, given as `(old, new)` pairs.
+    ///
+    /// Equivalent to calling `update` for every pair, except that all objects that
+    /// don't fit into their previous node are inserted after all other updates have
+    /// been applied. Thus, an object moved by this batch must not be updated again by
+    /// a later pair of the same batch.
+    ///
+    /// Returns the number of objects that were found and replaced.
+    pub fn update_many<I>(&mut self, updates: I) -> usize
+    where
+        I: IntoIterator<Item = (T, T)>,
+    {
+        let mut updated = 0;
+        let mut to_insert = Vec::new();
+        for (old, new) in updates {
+            match self.update_in_place(&old, new) {
+                UpdateResult::NotFound(_) => continue,
+                UpdateResult::Updated => {}
+                UpdateResult::Reinsert(new) => to_insert.push(new),
+            }
+            updated += 1;
+        }
+        for new in to_insert {
+            self.insert_node(RTreeNode::Leaf(new));
+        }
+        updated
+    }
+
+    fn update_in_place(&mut self, old: &T, new: T) -> UpdateResult<T> {
+        if self.size == 0 {
+            return UpdateResult::NotFound(new);
+        }
+        let result = self.root.update(old, new, &old.mbr());
+        if self.root.children.is_empty() {
+            self.root.depth = 1;
+        }
+        result
+    }
+}
+
+/// Returns the smallest number `r` with `r^n >= value`.
+fn nth_root_ceil(value: usize, n: usize) -> usize {
+    let mut result = (value as f64).powf(1.0 / n as f64).floor() as usize;
+    // Correct possible floating point inaccuracies
+    while result.pow(n as u32) < value {
+        result += 1;
+    }
+    while result > 1 && (result - 1).pow(n as u32) >= value {
+        result -= 1;
+    }
+    result.max(1)
+}
+
+#[inline]
+fn create_clusters<T: SpatialObject>(array: &mut [T], cluster_size: usize, dimension: usize) {
+    let comp = |l: &T, r: &T| {
+        let l_mbr = l.mbr();
+        let r_mbr = r.mbr();
+        l_mbr
+            .lower()
+            .nth(dimension)
+            .partial_cmp(&r_mbr.lower().nth(dimension))
+            .unwrap()
+    };
+
+    let mut cur = 0;
+    while cur <= array.len() {
+        ::pdqselect::select_by(&mut array[cur..], cluster_size, &comp);
+        cur += cluster_size;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        nth_root_ceil, Count, DirectoryNodeData, PackedRTree, RTree, RTreeNode, RTreeOptions,
+        RTreeOptionsError,
+    };
+    use crate::boundingrect::BoundingRect;
+    use crate::point_traits::PointNExtensions;
+    use crate::primitives::{SimpleEdge, SimpleTriangle};
+    use crate::testutils::*;
+    use crate::traits::SpatialObject;
+    use cgmath::{InnerSpace, Point2};
+    use num::Float;
+
+    #[test]
+    fn test_tree_with_integral_points() {
+        // This test should compile
+        let mut tree = RTree::new();
+        tree.insert(Point2::new(13, 37));
+        assert!(tree.lookup(&Point2::new(13, 37)).is_some())
+    }
+
+    #[test]
+    fn test_tree_with_array_points() {
+        // This test should compile
+        let mut tree = RTree::<[i32; 3]>::new();
+        tree.insert([13i32, 37, 12]);
+        assert!(tree.lookup(&[13, 37, 12]).is_some())
+    }
+
+    #[test]
+    fn test_nearest_neighbor_empty() {
+        let tree: RTree<Point2<f32>> = RTree::new();
+        assert!(tree.nearest_neighbor(&Point2::new(10., 20f32)).is_none());
+    }
+
+    #[test]
+    fn test_nearest_neighbor() {
+        let (tree, points) = create_random_tree::<f32>(1000, b"nearest-neighbor");
+        let sample_points = random_points_with_seed(100, b"aoppllb,1 0 s0,p");
+        for sample_point in &sample_points {
+            let mut nearest = None;
+            let mut closest_dist = Float::infinity();
+            for point in &points {
+                let new_dist = (point - sample_point).magnitude2();
+                if new_dist < closest_dist {
+                    closest_dist = new_dist;
+                    nearest = Some(point);
+                }
+            }
+            assert!(nearest == tree.nearest_neighbor(sample_point));
+        }
+    }
+
+    #[test]
+    fn test_nearest_neighbor_lines() {
+        let points = random_points_with_seed::<f32>(200, b"ThirstyChicken?=");
+        let lines: Vec<_> = points
+            .chunks(2)
+            .map(|xs| SimpleEdge::new(xs[0], xs[1]))
+            .collect();
+        let tree = RTree::bulk_load(lines.clone());
+        let sample_points = random_points_with_seed(1000, b"S)nug( life .rat");
+        for sample_point in &sample_points {
+            let mut nearest = None;
+            let mut closest_dist = Float::infinity();
+            for line in &lines {
+                let new_dist = line.distance2(sample_point);
+                if new_dist < closest_dist {
+                    closest_dist = new_dist;
+                    nearest = Some(line);
+                }
+            }
+            assert_eq!(nearest, tree.nearest_neighbor(sample_point));
+        }
+    }
+
+    #[test]
+    fn test_nearest_neighbor_iterator() {
+        let (tree, mut points) = create_random_tree::<f32>(100, b"switzerlandchees");
+        let sample_points = random_points_with_seed(10, b"15yummydelicious");
+        for sample_point in &sample_points {
+            points.sort_by(|l, r| {
+                l.distance2(sample_point)
+                    .partial_cmp(&r.distance2(sample_point))
+                    .unwrap()
+            });
+            let collected: Vec<_> = tree
+                .nearest_neighbor_iterator(sample_point)
+                .cloned()
+                .collect();
+            assert_eq!(points, collected);
+        }
+    }
+
+    #[test]
+    fn test_nearest_neighbor_iter_with_distance2() {
+        let (tree, points) = create_random_tree::<f32>(200, b"distance matters");
+        let sample_points = random_points_with_seed::<f32>(10, b"how far is it?!!");
+        for sample_point in &sample_points {
+            let mut last = 0.0;
+            let mut count = 0;
+            for (point, distance2) in tree.nearest_neighbor_iter_with_distance2(sample_point) {
+                assert_eq!(point.distance2(sample_point), distance2);
+                assert!(distance2 >= last);
+                last = distance2;
+                count += 1;
+            }
+            assert_eq!(count, points.len());
+        }
+    }
+
+    #[test]
+    fn test_nearest_n_neighbors_within() {
+        const MAX_DISTANCE2: f32 = 0.05;
+        let (tree, points) = create_random_tree::<f32>(300, b"close, but not t");
+        let sample_points = random_points_with_seed::<f32>(10, b"oo close, please");
+        for sample_point in &sample_points {
+            let within = points
+                .iter()
+                .filter(|p| p.distance2(sample_point) <= MAX_DISTANCE2)
+                .count();
+            for &n in &[0, 1, 5, within, within + 10] {
+                let result = tree.nearest_n_neighbors_within(sample_point, n, MAX_DISTANCE2);
+                assert_eq!(result.len(), n.min(within));
+                assert_eq!(
+                    result,
+                    tree.nearest_n_neighbors(sample_point, n.min(within))
+                );
+                assert!(result
+                    .iter()
+                    .all(|p| p.distance2(sample_point) <= MAX_DISTANCE2));
+            }
+        }
+        let empty: RTree<Point2<f32>> = RTree::new();
+        assert!(empty
+            .nearest_n_neighbors_within(&Point2::new(0.0, 0.0), 3, 1.0)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_nearest_neighbor_where() {
+        let mut tree = RTree::new();
+        let points = random_points_with_seed::<f32>(300, b"only the even on");
+        for (index, point) in points.iter().enumerate() {
+            tree.insert(PointWithData::new(*point, index as u32));
+        }
+        let sample_points = random_points_with_seed::<f32>(10, b"es may apply. :)");
+        for sample_point in &sample_points {
+            let expected = tree
+                .nearest_neighbor_iterator(sample_point)
+                .find(|p| p.data % 2 == 0)
+                .unwrap();
+            let result = tree
+                .nearest_neighbor_where(sample_point, |p| p.data % 2 == 0)
+                .unwrap();
+            assert_eq!(result.data, expected.data);
+            assert!(tree
+                .nearest_neighbor_where(sample_point, |p| p.data > 1000)
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_nearest_neighbor_iterator_empty() {
+        let tree: RTree<[f32; 2]> = RTree::new();
+        assert_eq!(tree.nearest_neighbor_iterator(&[0.0, 0.0]).next(), None);
+    }
+
+    #[test]
+    fn test_lookup_in_circle() {
+        let (tree, points) = create_random_tree::<f32>(1000, b"never turn your ");
+        let sample_points = random_points_with_seed(100, b"back on friends~");
+        const RADIUS: f32 = 20.;
+        for sample_point in &sample_points {
+            let mut expected = Vec::new();
+            for point in &points {
+                let new_dist = (point - sample_point).magnitude2();
+                if new_dist < RADIUS * RADIUS {
+                    expected.push(point);
+                }
+            }
+            let points = tree.lookup_in_circle(sample_point, &(RADIUS * RADIUS));
+            assert_eq!(points.len(), expected.len());
+            for p in &points {
+                assert!(expected.contains(p));
+            }
+            for p in &expected {
+                assert!(points.contains(p));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lookup_in_rect() {
+        use cgmath::{EuclideanSpace, Vector2};
+
+        let (tree, points) = create_random_tree::<f32>(1000, b"the heir of the ");
+        let sample_points = random_points_with_seed(100, b"highlord you bet");
+        const SIZE: f32 = 20.;
+        for sample_point in &sample_points {
+            let sample_rect = BoundingRect::from_corners(
+                sample_point,
+                &Point2::from_vec(sample_point.to_vec() + Vector2::new(SIZE, SIZE)),
+            );
+            let mut expected = Vec::new();
+            for point in &points {
+                if sample_rect.contains_point(point) {
+                    expected.push(point);
+                }
+            }
+            let points = tree.lookup_in_rectangle(&sample_rect);
+            assert_eq!(points.len(), expected.len());
+            for p in &points {
+                assert!(expected.contains(p));
+            }
+            for p in &expected {
+                assert!(points.contains(p));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lookup_iter() {
+        let (tree, _) = create_random_tree::<f32>(1000, b"Lazy bones dream");
+        let sample_points = random_points_with_seed(100, b"of sunny meadows");
+        for sample_point in &sample_points {
+            let rect = BoundingRect::from_corners(
+                sample_point,
+                &Point2::new(sample_point.x + 0.3, sample_point.y + 0.2),
+            );
+            let expected = tree.lookup_in_rectangle(&rect);
+            assert_eq!(tree.lookup_in_rectangle_iter(&rect).count(), expected.len());
+            for p in tree.lookup_in_rectangle_iter(&rect) {
+                assert!(expected.contains(&p));
+            }
+            let first: Vec<_> = tree.lookup_in_rectangle_iter(&rect).take(3).collect();
+            assert_eq!(first.len(), expected.len().min(3));
+
+            let expected = tree.lookup_in_circle(sample_point, &0.04);
+            assert_eq!(
+                tree.lookup_in_circle_iter(sample_point, &0.04).count(),
+                expected.len()
+            );
+            for p in tree.lookup_in_circle_iter(sample_point, &0.04) {
+                assert!(expected.contains(&p));
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearest_neighbors() {
+        let mut tree = RTree::new();
+        assert!(tree.nearest_neighbors(&Point2::new(1, 0)).is_empty());
+        tree.insert(Point2::new(1, 0));
+        tree.insert(Point2::new(0, 1));
+        tree.insert(Point2::new(-1, 0));
+        tree.insert(Point2::new(0, -1));
+        tree.insert(Point2::new(3, 0));
+        tree.insert(Point2::new(2, 1));
+        tree.insert(Point2::new(2, -1));
+        assert_eq!(tree.nearest_neighbors(&Point2::new(0, 0)).len(), 4);
+        assert_eq!(tree.nearest_neighbors(&Point2::new(1, 0)).len(), 1);
+        assert_eq!(tree.nearest_neighbors(&Point2::new(2, 0)).len(), 4);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let (mut tree, mut points) = create_random_tree::<f32>(10000, b"The enemy of min");
+        let sample_points = random_points_with_seed(1000, b"Isn't he of your");
+        for sample_point in &sample_points {
+            assert!(tree.lookup(sample_point).is_none());
+            assert!(tree.lookup_mut(sample_point).is_none());
+        }
+        for point in points.iter_mut() {
+            assert!(tree.lookup(point) == Some(point));
+            assert!(tree.lookup_mut(point) == Some(point));
+        }
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let points = random_points_with_seed::<f32>(500, b"The mill turns a");
+        let mut tree = RTree::bulk_load(points.iter().map(|p| PointWithData::new(*p, 0)).collect());
+        for mut object in tree.iter_mut() {
+            object.data += 1;
+        }
+        assert_eq!(tree.iter().count(), points.len());
+        assert!(tree.iter().all(|object| object.data == 1));
+    }
+
+    #[test]
+    fn test_lookup_mut_queries() {
+        let points = random_points_with_seed::<f32>(1000, b"nd the wheel cre");
+        let mut tree = RTree::bulk_load(points.iter().map(|p| PointWithData::new(*p, 0)).collect());
+        let rect = BoundingRect::from_corners(&Point2::new(-0.5, -0.2), &Point2::new(0.3, 0.4));
+        for mut object in tree.lookup_in_rectangle_mut(&rect) {
+            object.data += 1;
+        }
+        let origin = Point2::new(0.2, 0.1);
+        for mut object in tree.lookup_in_circle_mut(&origin, &0.25) {
+            object.data += 2;
+        }
+        for object in tree.iter() {
+            let mut expected = 0;
+            if rect.contains_point(&object.position) {
+                expected += 1;
+            }
+            if object.position.distance2(&origin) < 0.25 {
+                expected += 2;
+            }
+            assert_eq!(object.data, expected);
+        }
+    }
+
+    #[test]
+    fn test_nearest_neighbor_mut() {
+        let points = random_points_with_seed::<f32>(1000, b"aks, as the stre");
+        let mut tree = RTree::bulk_load(points.iter().map(|p| PointWithData::new(*p, 0)).collect());
+        let sample_points = random_points_with_seed(100, b"am keeps flowing");
+        for (index, sample_point) in sample_points.iter().enumerate() {
+            let expected = tree.nearest_neighbor(sample_point).unwrap().position;
+            let mut nearest = tree.nearest_neighbor_mut(sample_point).unwrap();
+            assert_eq!(nearest.position, expected);
+            nearest.data = index as u32 + 1;
+        }
+        let last = sample_points.last().unwrap();
+        assert_eq!(
+            tree.nearest_neighbor(last).unwrap().data,
+            sample_points.len() as u32
+        );
+        let mut empty: RTree<PointWithData> = RTree::new();
+        assert!(empty.nearest_neighbor_mut(last).is_none());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_iter_mut_changed_mbr() {
+        let (mut tree, _) = create_random_tree::<f32>(10, b"Such is the cycl");
+        for mut point in tree.iter_mut() {
+            point.x += 1.0;
+        }
+    }
+
+    #[test]
+    fn test_lookup_and_remove() {
+        let (mut tree, points) = create_random_tree::<f32>(10000, b"kind? And finall");
+        let sample_points = random_points_with_seed(1000, b"you may follow m");
+        for sample_point in &sample_points {
+            assert!(!tree.lookup_and_remove(sample_point).is_some());
+        }
+
+        // Test if all points are still there
+        for point in &points {
+            assert_eq!(tree.lookup(point), Some(point));
+        }
+        // Now remove all points
+        for point in &points {
+            assert_eq!(tree.lookup_and_remove(point).as_ref(), Some(point));
+        }
+        assert!(tree.root.children.is_empty());
+        tree.insert(Point2::new(20., 10.));
+    }
+
+    fn check_min_size<T: SpatialObject>(node: &DirectoryNodeData<T>, is_root: bool) {
+        if !is_root {
+            assert!(node.children.len() >= node.options.min_size);
+        }
+        for child in &node.children {
+            if let RTreeNode::DirectoryNode(ref data) = child {
+                check_min_size(data, false);
+            }
+        }
+    }
+
+    #[test]
+    fn test_drain_in_rectangle() {
+        let (mut tree, points) = create_random_tree::<f32>(2000, b"Dust to dust and");
+        let rect = BoundingRect::from_corners(&Point2::new(-0.5, -0.8), &Point2::new(0.7, 0.3));
+        let mut drained = tree.drain_in_rectangle(&rect);
+        let mut expected: Vec<_> = points
+            .iter()
+            .cloned()
+            .filter(|p| rect.contains_point(p))
+            .collect();
+        assert_eq!(drained.len(), expected.len());
+        drained.sort_by(|l, r| l.lex_compare(r));
+        expected.sort_by(|l, r| l.lex_compare(r));
+        assert_eq!(drained, expected);
+
+        assert_eq!(tree.size(), points.len() - expected.len());
+        assert_eq!(check_invariants(&tree.root), tree.size());
+        check_min_size(&tree.root, true);
+        for p in &points {
+            assert_eq!(tree.lookup(p).is_some(), !rect.contains_point(p));
+        }
+        assert!(tree.drain_in_rectangle(&rect).is_empty());
+    }
+
+    #[test]
+    fn test_drain_in_circle() {
+        let (mut tree, points) = create_random_tree::<f32>(1000, b" ashes to ashes.");
+        let origin = Point2::new(0.1, -0.2);
+        let drained = tree.drain_in_circle(&origin, &0.3);
+        let expected = points.iter().filter(|p| p.distance2(&origin) < 0.3).count();
+        assert_eq!(drained.len(), expected);
+        assert_eq!(tree.size(), points.len() - expected);
+        assert_eq!(check_invariants(&tree.root), tree.size());
+        check_min_size(&tree.root, true);
+        assert!(tree.lookup_in_circle(&origin, &0.3).is_empty());
+    }
+
+    #[test]
+    fn test_retain() {
+        let (mut tree, points) = create_random_tree::<f32>(1000, b"A new life sprou");
+        tree.retain(|p| p.x * p.y > 0.0);
+        let expected: Vec<_> = points.iter().filter(|p| p.x * p.y > 0.0).collect();
+        assert_eq!(tree.size(), expected.len());
+        assert_eq!(check_invariants(&tree.root), tree.size());
+        check_min_size(&tree.root, true);
+        for p in expected {
+            assert!(tree.lookup(p).is_some());
+        }
+
+        // Remove almost everything
+        let mut count = 0;
+        tree.retain(|_| {
+            count += 1;
+            count % 100 == 0
+        });
+        assert_eq!(tree.size(), count / 100);
+        assert_eq!(check_invariants(&tree.root), tree.size());
+        check_min_size(&tree.root, true);
+
+        tree.retain(|_| false);
+        assert_eq!(tree.size(), 0);
+        assert_eq!(tree.mbr(), None);
+        tree.insert(Point2::new(0.0, 0.0));
+        assert_eq!(tree.size(), 1);
+    }
+
+    #[test]
+    fn test_remove() {
+        let random_points = random_points_with_seed(300, b"Farewell, he sai");
+        let mut triangles = Vec::with_capacity(100);
+        for ps in random_points.chunks(3) {
+            triangles.push(SimpleTriangle::new(ps[0], ps[1], ps[2]));
+        }
+
+        for ps in random_points.chunks(3) {
+            // Insert every triangle twice
+            triangles.push(SimpleTriangle::new(ps[2], ps[1], ps[0]));
+        }
+
+        let mut tree = RTree::new();
+        for _ in 0..2 {
+            for triangle in triangles.iter().cloned() {
+                tree.insert(triangle);
+            }
+
+            // Try to remove a triangle that is not contained
+            let triangle = SimpleTriangle::new(
+                Point2::new(0.0, 0.0),
+                Point2::new(1.0, 0.0),
+                Point2::new(1.0, 1.0),
+            );
+            assert!(!tree.remove(&triangle));
+            let mut size = 200usize;
+            for triangle in &triangles {
+                assert!(tree.remove(triangle));
+                size -= 1;
+                assert_eq!(tree.size(), size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove_line() {
+        let mut tree = RTree::new();
+        let edge = SimpleEdge::new([0f32, 0.], [1., 1.]);
+        tree.insert(edge);
+        tree.insert(SimpleEdge::new([3., 4.], [0., 2.]));
+        tree.insert(SimpleEdge::new([-3., -4.], [0., 2.]));
+        tree.remove(&edge);
+        assert_eq!(tree.size(), 2);
+    }
+
+    #[test]
+    fn test_update() {
+        let (mut tree, mut points) = create_random_tree::<f64>(1000, b"Keep on moving o");
+        for step in 0..10 {
+            let offset = Point2::new(0.001 * step as f64, -0.002);
+            for point in points.iter_mut() {
+                let new = Point2::new(point.x + offset.x, point.y + offset.y);
+                assert!(tree.update(point, new));
+                *point = new;
+            }
+            assert_eq!(tree.size(), points.len());
+            assert_eq!(check_invariants(&tree.root), tree.size());
+        }
+        // Far jumps fall back to reinsertion
+        for point in points.iter_mut().step_by(3) {
+            let new = Point2::new(-point.x * 10.0, point.y * 10.0);
+            assert!(tree.update(point, new));
+            *point = new;
+        }
+        assert_eq!(check_invariants(&tree.root), tree.size());
+        for point in &points {
+            assert_eq!(tree.lookup(point), Some(point));
+        }
+        assert!(!tree.update(&Point2::new(5.0, 5.0), Point2::new(0.0, 0.0)));
+        assert_eq!(tree.size(), points.len());
+
+        let mut tree = RTree::new();
+        tree.insert(Point2::new(0.0, 0.0));
+        assert!(tree.update(&Point2::new(0.0, 0.0), Point2::new(1.0, 1.0)));
+        assert_eq!(
+            tree.mbr(),
+            Some(BoundingRect::from_point(Point2::new(1.0, 1.0)))
+        );
+    }
+
+    #[test]
+    fn test_update_many() {
+        let (mut tree, points) = create_random_tree::<f64>(1000, b"Move along, move");
+        let targets = random_points_with_seed::<f64>(1000, b"..nothing to see");
+        let moved: Vec<_> = points
+            .iter()
+            .zip(targets.iter())
+            .enumerate()
+            .map(|(index, (old, target))| {
+                if index % 2 == 0 {
+                    (*old, *target)
+                } else {
+                    (*old, Point2::new(old.x + 1e-4, old.y))
+                }
+            })
+            .collect();
+        assert_eq!(tree.update_many(moved.iter().cloned()), points.len());
+        assert_eq!(tree.size(), points.len());
+        assert_eq!(check_invariants(&tree.root), tree.size());
+        for (_, new) in &moved {
+            assert_eq!(tree.lookup(new), Some(new));
+        }
+        let missing = (Point2::new(3.0, 3.0), Point2::new(0.0, 0.0));
+        assert_eq!(tree.update_many(vec![missing]), 0);
+    }
+
+    #[test]
+    fn test_bulk_insert() {
+        let (mut tree, mut points) = create_random_tree::<f64>(1000, b"Chunk by chunk..");
+        for (index, seed) in [
+            b"First chunk.....",
+            b"Second chunk....",
+            b"Tiny chunk......",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let chunk = random_points_with_seed::<f64>([2000, 300, 3][index], seed);
+            tree.bulk_insert(chunk.clone());
+            points.extend(chunk);
+            assert_eq!(tree.size(), points.len());
+            assert_eq!(check_invariants(&tree.root), tree.size());
+        }
+        for point in &points {
+            assert_eq!(tree.lookup(point), Some(point));
+        }
+        tree.bulk_insert(Vec::new());
+        assert_eq!(tree.size(), points.len());
+
+        let mut empty = RTree::new();
+        empty.bulk_insert(points.clone());
+        assert_eq!(check_invariants(&empty.root), points.len());
+    }
+
+    /// Returns the average number of children per directory node, relative to the
+    /// maximal node size.
+    fn average_fill<T: SpatialObject>(root: &DirectoryNodeData<T>) -> f64 {
+        let mut nodes = 0;
+        let mut children = 0;
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            nodes += 1;
+            children += node.children.len();
+            for child in &node.children {
+                if let RTreeNode::DirectoryNode(ref data) = child {
+                    stack.push(data);
+                }
+            }
+        }
+        children as f64 / (nodes * root.options.max_size) as f64
+    }
+
+    #[test]
+    fn test_extend_one_by_one() {
+        let points = random_points_with_seed::<f64>(2000, b"One at a time...");
+        let mut extended = RTree::new();
+        let mut inserted = RTree::new();
+        for point in &points {
+            extended.extend(::std::iter::once(*point));
+            inserted.insert(*point);
+        }
+        assert_eq!(check_invariants(&extended.root), points.len());
+        check_min_size(&extended.root, true);
+        assert!(extended.root.depth <= inserted.root.depth);
+        assert!(average_fill(&extended.root) > 0.9 * average_fill(&inserted.root));
+        for point in &points {
+            assert_eq!(extended.lookup(point), Some(point));
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let (mut small, small_points) = create_random_tree::<f64>(50, b"The small tree..");
+        let (large, large_points) = create_random_tree::<f64>(2000, b"The large tree..");
+        small.set_aggregator(Count);
+        small.merge(large);
+        assert_eq!(small.size(), 2050);
+        assert_eq!(check_invariants(&small.root), 2050);
+        for point in small_points.iter().chain(large_points.iter()) {
+            assert_eq!(small.lookup(point), Some(point));
+        }
+        let everything =
+            BoundingRect::from_corners(&Point2::new(-2.0, -2.0), &Point2::new(2.0, 2.0));
+        assert_eq!(
+            small.aggregate_in_rectangle::<Count>(&everything),
+            Some(2050)
+        );
+
+        let mut other: RTree<_> = RTreeOptions::new().max_size(10).build();
+        other.extend(small_points.iter().cloned());
+        small.merge(other);
+        assert_eq!(check_invariants(&small.root), 2100);
+
+        // Trees that only differ in their bulk load size are grafted, the merged tree
+        // keeps its own options
+        let options = RTreeOptions::new().bulk_load_size(4);
+        assert!(options.has_same_insertion_parameters(&RTreeOptions::new()));
+        assert!(!options.has_same_insertion_parameters(&RTreeOptions::new().max_size(10)));
+        let mut loaded = RTree::new_with_options(options);
+        loaded.bulk_insert(small_points.clone());
+        let (large, _) = create_random_tree::<f64>(1000, b"Same but differe");
+        let large_depth = large.root.depth;
+        loaded.merge(large);
+        assert_eq!(*loaded.root.options, options);
+        assert_eq!(loaded.root.depth, large_depth);
+        assert_eq!(check_invariants(&loaded.root), 1050);
+
+        let collected: RTree<_> = large_points.iter().cloned().collect();
+        assert_eq!(collected.size(), large_points.len());
+        assert_eq!(check_invariants(&collected.root), large_points.len());
+    }
+
+    #[test]
+    fn test_iteration() {
+        let (tree, reference_points) = create_random_tree::<f32>(100, b"Nightfall~quietl");
+        // Check if the set of reference points and the points given by
+        // iteration are equal
+        assert_eq!(tree.iter().count(), 100);
+        let points: Vec<_> = tree.iter().cloned().collect();
+        for p in points.iter() {
+            assert!(reference_points.contains(p));
+        }
+        for p in reference_points.iter() {
+            assert!(points.contains(p));
+        }
+    }
+
+    #[test]
+    fn test_higher_dimensions() {
+        use nalgebra::Point4;
+        use rand::distributions::{Distribution, Standard};
+        use rand::{SeedableRng, XorShiftRng};
+
+        let mut tree: RTree<Point4<f32>> = RTree::new();
+        let mut rng = XorShiftRng::from_seed(*b"crept in and cha");
+        let mut entries = Vec::new();
+        for _ in 0..500 {
+            let (x, y, z, w) = Standard.sample(&mut rng);
+            let entry = Point4::new(x, y, z, w);
+            entries.push(entry);
+            tree.insert(entry);
+        }
+
+        for entry in &entries {
+            assert!(tree.lookup(entry).is_some());
+            assert_eq!(tree.nearest_neighbor(entry), Some(entry))
+        }
+    }
+
+    #[test]
+    fn test_bulk_load() {
+        const MAX_POINTS: usize = 200;
+        let points = random_points_with_seed::<f32>(MAX_POINTS, b"Immortal lands l");
+
+        // Check if no number of points crashes
+        let mut tree = RTree::new();
+        for num in 0..MAX_POINTS {
+            tree = RTree::bulk_load(points[..=num].to_vec());
+            assert_eq!(check_invariants(&tree.root), num + 1);
+        }
+        assert_eq!(tree.iter().count(), MAX_POINTS);
+        // Check if all points have been inserted
+        for p in &points {
+            assert!(tree.lookup(p).is_some());
+        }
+    }
+
+    fn check_invariants<T: SpatialObject>(node: &DirectoryNodeData<T>) -> usize {
+        assert!(node.children.len() <= node.options.max_size);
+        let mut mbr = node.children.first().map(|child| child.mbr());
+        let mut size = 0;
+        for child in &node.children {
+            mbr.as_mut().unwrap().add_rect(&child.mbr());
+            assert_eq!(child.depth() + 1, node.depth);
+            size += match child {
+                RTreeNode::DirectoryNode(ref data) => check_invariants(data),
+                RTreeNode::Leaf(_) => 1,
+            };
+        }
+        assert_eq!(mbr, node.bounding_box);
+        size
+    }
+
+    #[test]
+    fn test_bulk_load_three_dimensional() {
+        use rand::distributions::{Distribution, Standard};
+        use rand::{SeedableRng, XorShiftRng};
+
+        const MAX_POINTS: usize = 300;
+        let mut rng = XorShiftRng::from_seed(*b"Through the dust");
+        let points: Vec<[f64; 3]> = (0..MAX_POINTS).map(|_| Standard.sample(&mut rng)).collect();
+        for num in 1..MAX_POINTS {
+            let tree = RTree::bulk_load(points[..=num].to_vec());
+            assert_eq!(check_invariants(&tree.root), num + 1);
+            assert_eq!(tree.size(), num + 1);
+        }
+        let tree = RTree::bulk_load(points.clone());
+        for p in &points {
+            assert_eq!(tree.lookup(p), Some(p));
+            assert_eq!(tree.nearest_neighbor(p), Some(p));
+        }
+    }
+
+    #[test]
+    fn test_bulk_load_higher_dimensions() {
+        use nalgebra::Point4;
+        use rand::distributions::{Distribution, Standard};
+        use rand::{SeedableRng, XorShiftRng};
+
+        let mut rng = XorShiftRng::from_seed(*b"and the ashes, w");
+        let points: Vec<_> = (0..2000)
+            .map(|_| {
+                let (x, y, z, w) = Standard.sample(&mut rng);
+                Point4::new(x, y, z, w)
+            })
+            .collect();
+        let options = RTreeOptions::new().max_size(9).min_size(4);
+        let tree = RTree::<Point4<f32>>::bulk_load_with_options(options, points.clone());
+        assert_eq!(check_invariants(&tree.root), points.len());
+        for p in &points {
+            assert!(tree.lookup(p).is_some());
+        }
+    }
+
+    #[test]
+    fn test_nth_root_ceil() {
+        assert_eq!(nth_root_ceil(1, 3), 1);
+        assert_eq!(nth_root_ceil(8, 3), 2);
+        assert_eq!(nth_root_ceil(9, 3), 3);
+        assert_eq!(nth_root_ceil(16, 2), 4);
+        assert_eq!(nth_root_ceil(17, 2), 5);
+        assert_eq!(nth_root_ceil(81, 4), 3);
+    }
+
+    #[test]
+    fn test_options_validation() {
+        let options = RTreeOptions::new();
+        assert_eq!(options.validate(), Ok(()));
+        assert_eq!(
+            options.try_max_size(3),
+            Err(RTreeOptionsError::MaxSizeNotLargerThanMinSize {
+                max_size: 3,
+                min_size: 3
+            })
+        );
+        assert_eq!(
+            options.try_min_size(7).unwrap_err(),
+            RTreeOptionsError::MaxSizeNotLargerThanMinSize {
+                max_size: 6,
+                min_size: 7
+            }
+        );
+        assert_eq!(
+            options.try_reinsertion_count(0),
+            Err(RTreeOptionsError::ZeroReinsertionCount)
+        );
+        assert_eq!(
+            options.try_reinsertion_count(6),
+            Err(RTreeOptionsError::ReinsertionCountTooLarge {
+                reinsertion_count: 6,
+                max_size: 6
+            })
+        );
+        assert_eq!(
+            options.try_bulk_load_size(1),
+            Err(RTreeOptionsError::BulkLoadSizeOutOfRange {
+                bulk_load_size: 1,
+                min_size: 3,
+                max_size: 6
+            })
+        );
+        // Bulk loaded nodes must not be underfull
+        assert!(options.try_bulk_load_size(2).is_err());
+        assert!(options.try_bulk_load_size(3).is_ok());
+        assert!(options
+            .try_bulk_load_size(3)
+            .and_then(|options| options.try_min_size(4))
+            .is_err());
+        assert!(options
+            .try_min_size(1)
+            .and_then(|options| options.try_bulk_load_size(2))
+            .is_ok());
+        let options = options
+            .try_max_size(12)
+            .and_then(|options| options.try_min_size(5))
+            .and_then(|options| options.try_reinsertion_count(8))
+            .and_then(|options| options.try_bulk_load_size(10))
+            .unwrap();
+        assert_eq!(options.validate(), Ok(()));
+        // Lowering the maximum size must keep the other parameters valid
+        assert!(options.try_max_size(8).is_err());
+        assert!(options
+            .try_reinsertion_count(4)
+            .unwrap()
+            .try_max_size(9)
+            .is_err());
+        assert!(options
+            .try_reinsertion_count(4)
+            .and_then(|options| options.try_bulk_load_size(6))
+            .and_then(|options| options.try_max_size(6))
+            .is_ok());
+        assert_eq!(
+            RTreeOptionsError::ZeroReinsertionCount.to_string(),
+            "Reinsertion cannot be zero"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must be larger than min size")]
+    fn test_invalid_options_panic() {
+        RTreeOptions::new().max_size(2);
+    }
+
+    #[test]
+    fn test_bulk_load_size() {
+        let points = random_points_with_seed::<f64>(1000, b"Leave some room.");
+        let options = RTreeOptions::new().max_size(10).bulk_load_size(7);
+        let mut tree = RTree::bulk_load_with_options(options, points.clone());
+        assert_eq!(check_invariants(&tree.root), points.len());
+        check_max_children(&tree.root, 7);
+        for point in random_points_with_seed::<f64>(100, b"..for later use.") {
+            tree.insert(point);
+        }
+        assert_eq!(check_invariants(&tree.root), points.len() + 100);
+
+        let packed = PackedRTree::bulk_load_with_options(options, points.clone());
+        assert_eq!(packed.size(), points.len());
+        for point in &points {
+            assert_eq!(packed.lookup(point), Some(point));
+        }
+    }
+
+    fn check_max_children<T: SpatialObject>(node: &DirectoryNodeData<T>, max_children: usize) {
+        assert!(node.children.len() <= max_children);
+        for child in &node.children {
+            if let RTreeNode::DirectoryNode(ref data) = child {
+                check_max_children(data, max_children);
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialization() {
+        use serde_json;
+        const SIZE: usize = 1000;
+        let points = random_points_with_seed::<f32>(SIZE, b"No sign of life ");
+        let tree = RTree::bulk_load(points.clone());
+        let json = serde_json::to_string(&tree).expect("Serializing tree failed");
+        let parsed: RTree<Point2<f32>> =
+            serde_json::from_str(&json).expect("Deserializing tree failed");
+        assert_eq!(parsed.size(), SIZE);
+        for point in &points {
+            assert!(parsed.contains(point));
+        }
+    }
+}
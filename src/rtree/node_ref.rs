@@ -0,0 +1,250 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{DirectoryNodeData, RTreeIterator, RTreeNode};
+use crate::boundingrect::BoundingRect;
+use crate::traits::SpatialObject;
+
+/// A read-only reference to a directory node of an r-tree.
+///
+/// Directory nodes contain other directory nodes or, on the lowest level, the inserted
+/// objects. Use `RTree::root_node` to start a traversal. This can be used to visualize
+/// the tree's structure or to implement custom queries.
+///
+/// Unlike the tree's internal node types, this interface is part of spade's stable API.
+///
+/// # Example
+/// ```
+/// extern crate cgmath;
+/// extern crate spade;
+///
+/// use cgmath::Point2;
+/// use spade::rtree::{RTree, RTreeNodeRef};
+///
+/// fn main() {
+///     let points: Vec<_> = (0..100).map(|i| Point2::new(i as f32, 0.0)).collect();
+///     let tree = RTree::bulk_load(points);
+///     // Count all directory nodes
+///     let mut to_visit: Vec<_> = tree.root_node().into_iter().collect();
+///     let mut num_directories = 0;
+///     while let Some(node) = to_visit.pop() {
+///         num_directories += 1;
+///         for child in node.children() {
+///             if let RTreeNodeRef::Directory(directory) = child {
+///                 to_visit.push(directory);
+///             }
+///         }
+///     }
+///     assert!(num_directories > 1);
+/// }
+/// ```
+pub struct DirectoryNodeRef<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    data: &'a DirectoryNodeData<T>,
+}
+
+/// A read-only reference to any node of an r-tree.
+///
+/// Returned when iterating over the children of a `DirectoryNodeRef`.
+pub enum RTreeNodeRef<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    /// A contained object.
+    Leaf(&'a T),
+    /// A directory node containing other nodes.
+    Directory(DirectoryNodeRef<'a, T>),
+}
+
+/// An iterator over the children of a directory node.
+///
+/// This `struct` is created by `DirectoryNodeRef::children`.
+pub struct DirectoryChildrenIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    iter: ::std::slice::Iter<'a, RTreeNode<T>>,
+}
+
+impl<'a, T> DirectoryNodeRef<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    pub(super) fn new(data: &'a DirectoryNodeData<T>) -> Self {
+        DirectoryNodeRef { data }
+    }
+
+    /// Returns the node's depth.
+    ///
+    /// Directory nodes that only contain objects have depth 1, every other
+    /// directory node is one level deeper than its children. The root's depth is
+    /// the tree's height.
+    pub fn depth(&self) -> usize {
+        self.data.depth
+    }
+
+    /// Returns the node's minimal bounding rectangle, containing all objects of this
+    /// subtree.
+    pub fn mbr(&self) -> BoundingRect<T::Point> {
+        self.data.mbr()
+    }
+
+    /// Returns the number of direct children.
+    pub fn len(&self) -> usize {
+        self.data.children.len()
+    }
+
+    /// Returns `true` if this node has no children.
+    ///
+    /// Only the root of an empty tree can be empty, thus this never returns `true` for
+    /// nodes returned by `RTree::root_node`.
+    pub fn is_empty(&self) -> bool {
+        self.data.children.is_empty()
+    }
+
+    /// Returns an iterator over the node's direct children.
+    pub fn children(&self) -> DirectoryChildrenIterator<'a, T> {
+        DirectoryChildrenIterator {
+            iter: self.data.children.iter(),
+        }
+    }
+
+    /// Returns an iterator over all objects contained in this subtree.
+    pub fn leaves(&self) -> RTreeIterator<'a, T> {
+        RTreeIterator::new(self.data)
+    }
+}
+
+impl<'a, T> Clone for DirectoryNodeRef<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for DirectoryNodeRef<'a, T> where T: SpatialObject + 'a {}
+
+impl<'a, T> RTreeNodeRef<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    fn new(node: &'a RTreeNode<T>) -> Self {
+        match node {
+            RTreeNode::Leaf(ref t) => RTreeNodeRef::Leaf(t),
+            RTreeNode::DirectoryNode(ref data) => {
+                RTreeNodeRef::Directory(DirectoryNodeRef::new(data))
+            }
+        }
+    }
+
+    /// Returns the node's depth, objects have depth 0.
+    pub fn depth(&self) -> usize {
+        match self {
+            RTreeNodeRef::Leaf(_) => 0,
+            RTreeNodeRef::Directory(ref directory) => directory.depth(),
+        }
+    }
+
+    /// Returns the node's minimal bounding rectangle.
+    pub fn mbr(&self) -> BoundingRect<T::Point> {
+        match self {
+            RTreeNodeRef::Leaf(t) => t.mbr(),
+            RTreeNodeRef::Directory(ref directory) => directory.mbr(),
+        }
+    }
+}
+
+impl<'a, T> Clone for RTreeNodeRef<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for RTreeNodeRef<'a, T> where T: SpatialObject + 'a {}
+
+impl<'a, T> Iterator for DirectoryChildrenIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    type Item = RTreeNodeRef<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(RTreeNodeRef::new)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for DirectoryChildrenIterator<'a, T> where T: SpatialObject + 'a {}
+
+#[cfg(test)]
+mod test {
+    use super::RTreeNodeRef;
+    use crate::rtree::RTree;
+    use crate::testutils::*;
+    use cgmath::Point2;
+
+    #[test]
+    fn test_node_ref_traversal() {
+        let (tree, points) = create_random_tree::<f32>(500, b"Walk the tree, c");
+        let root = tree.root_node().unwrap();
+        assert_eq!(root.leaves().count(), points.len());
+
+        let mut to_visit = vec![root];
+        let mut num_leaves = 0;
+        while let Some(node) = to_visit.pop() {
+            assert!(!node.is_empty());
+            assert_eq!(node.children().len(), node.len());
+            let mbr = node.mbr();
+            for child in node.children() {
+                assert_eq!(child.depth() + 1, node.depth());
+                assert!(mbr.contains_rect(&child.mbr()));
+                match child {
+                    RTreeNodeRef::Leaf(point) => {
+                        assert!(points.contains(point));
+                        num_leaves += 1;
+                    }
+                    RTreeNodeRef::Directory(directory) => {
+                        assert_eq!(
+                            directory.leaves().count(),
+                            directory
+                                .children()
+                                .map(|c| match c {
+                                    RTreeNodeRef::Leaf(_) => 1,
+                                    RTreeNodeRef::Directory(d) => d.leaves().count(),
+                                })
+                                .sum::<usize>()
+                        );
+                        to_visit.push(directory);
+                    }
+                }
+            }
+        }
+        assert_eq!(num_leaves, points.len());
+    }
+
+    #[test]
+    fn test_node_ref_empty() {
+        let tree: RTree<Point2<f32>> = RTree::new();
+        assert!(tree.root_node().is_none());
+        let mut tree = RTree::new();
+        tree.insert(Point2::new(1.0f32, 0.0));
+        let root = tree.root_node().unwrap();
+        assert_eq!(root.depth(), 1);
+        assert_eq!(root.len(), 1);
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(debug_assertions)]
+use crate::boundingrect::BoundingRect;
+use crate::traits::SpatialObject;
+use std::ops::{Deref, DerefMut};
+
+/// A mutable reference to an object contained in an r-tree.
+///
+/// Returned by the mutable query methods of `RTree`, e.g. `iter_mut` or
+/// `nearest_neighbor_mut`. Dereferences to the object itself and can be used to change
+/// any data that does not affect the object's position.
+///
+/// *The object's minimal bounding box must not be changed*, otherwise the tree may
+/// return wrong results. In debug builds, this is checked once the reference is dropped.
+pub struct RTreeObjectMut<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    object: &'a mut T,
+    #[cfg(debug_assertions)]
+    original_mbr: BoundingRect<T::Point>,
+}
+
+impl<'a, T> RTreeObjectMut<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    pub(super) fn new(object: &'a mut T) -> Self {
+        RTreeObjectMut {
+            #[cfg(debug_assertions)]
+            original_mbr: object.mbr(),
+            object,
+        }
+    }
+}
+
+impl<'a, T> Deref for RTreeObjectMut<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.object
+    }
+}
+
+impl<'a, T> DerefMut for RTreeObjectMut<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        self.object
+    }
+}
+
+impl<'a, T> Drop for RTreeObjectMut<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            if !::std::thread::panicking() {
+                assert!(
+                    self.object.mbr() == self.original_mbr,
+                    "The minimal bounding box of an object contained in an r-tree must not be changed"
+                );
+            }
+        }
+    }
+}
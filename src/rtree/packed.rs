@@ -0,0 +1,520 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{
+    nth_root_ceil, DistanceWrapper, RTreeOptions, SelectAtPoint, SelectInCircle, SelectInRectangle,
+    SelectionFunction,
+};
+use crate::boundingrect::BoundingRect;
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+use smallvec::SmallVec;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::ops::Range;
+
+#[cfg(feature = "serde_serialize")]
+use serde::{Deserialize, Serialize};
+
+type TodoList = SmallVec<[usize; 24]>;
+
+/// A directory node of a `PackedRTree`.
+///
+/// The node's children are stored in the range `start..end` of the tree's
+/// objects (for nodes on the lowest level) or nodes (for all other nodes).
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+struct PackedNode<V: PointN> {
+    mbr: BoundingRect<V>,
+    start: usize,
+    end: usize,
+}
+
+/// An immutable r-tree stored in flat, contiguous arrays.
+///
+/// A packed r-tree is created once from all of its objects and cannot be changed
+/// afterwards. All objects are stored in a single vector, all directory nodes in another
+/// one, each node referencing a contiguous range of children. Compared to `RTree`, this
+/// requires much less memory and improves cache locality of queries.
+///
+/// The objects are sorted with the Sort-Tile-Recursive (STR) algorithm, each level of
+/// directory nodes is sorted the same way before being packed into completely filled
+/// parent nodes.
+///
+/// # Example
+/// ```
+/// extern crate cgmath;
+/// extern crate spade;
+///
+/// use cgmath::Point2;
+/// use spade::rtree::PackedRTree;
+///
+/// fn main() {
+///     let points: Vec<_> = (0..1000).map(|i| Point2::new((i % 40) as f64, (i / 40) as f64)).collect();
+///     let tree = PackedRTree::bulk_load(points);
+///     assert_eq!(tree.nearest_neighbor(&Point2::new(3.2, 4.9)), Some(&Point2::new(3.0, 5.0)));
+///     assert_eq!(tree.lookup_in_circle(&Point2::new(0.0, 0.0), &1.5).len(), 3);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_serialize",
+    serde(bound(
+        serialize = "T: Serialize, T::Point: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Point: Deserialize<'de>"
+    ))
+)]
+pub struct PackedRTree<T>
+where
+    T: SpatialObject,
+{
+    objects: Vec<T>,
+    nodes: Vec<PackedNode<T::Point>>,
+    // Nodes with a smaller index contain objects, all other nodes contain nodes
+    num_leaf_nodes: usize,
+}
+
+impl<T> PackedRTree<T>
+where
+    T: SpatialObject,
+{
+    /// Creates a packed r-tree containing the given elements.
+    pub fn bulk_load(elements: Vec<T>) -> PackedRTree<T> {
+        Self::bulk_load_with_options(Default::default(), elements)
+    }
+
+    /// Creates a packed r-tree, using the options' bulk load size as the number of
+    /// children per node.
+    pub fn bulk_load_with_options(options: RTreeOptions, mut elements: Vec<T>) -> PackedRTree<T> {
+        let node_size = ::std::cmp::max(options.bulk_load_node_size(), 2);
+        str_sort(&mut elements, node_size, 0, &|t: &T| t.mbr().center());
+        let mut nodes: Vec<_> = elements
+            .chunks(node_size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let start = index * node_size;
+                PackedNode {
+                    mbr: chunk_mbr(chunk.iter().map(|t| t.mbr())),
+                    start,
+                    end: start + chunk.len(),
+                }
+            })
+            .collect();
+        let num_leaf_nodes = nodes.len();
+
+        let mut level_start = 0;
+        while nodes.len() - level_start > 1 {
+            let level = &mut nodes[level_start..];
+            str_sort(level, node_size, 0, &|node: &PackedNode<T::Point>| {
+                node.mbr.center()
+            });
+            let parents: Vec<_> = level
+                .chunks(node_size)
+                .enumerate()
+                .map(|(index, chunk)| {
+                    let start = level_start + index * node_size;
+                    PackedNode {
+                        mbr: chunk_mbr(chunk.iter().map(|node| node.mbr.clone())),
+                        start,
+                        end: start + chunk.len(),
+                    }
+                })
+                .collect();
+            level_start = nodes.len();
+            nodes.extend(parents);
+        }
+        nodes.shrink_to_fit();
+        PackedRTree {
+            objects: elements,
+            nodes,
+            num_leaf_nodes,
+        }
+    }
+
+    /// Returns the number of contained objects.
+    pub fn size(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns the tree's minimal bounding box.
+    pub fn mbr(&self) -> Option<BoundingRect<T::Point>> {
+        self.nodes.last().map(|root| root.mbr.clone())
+    }
+
+    /// Returns an iterator over all contained objects.
+    pub fn iter(&self) -> ::std::slice::Iter<'_, T> {
+        self.objects.iter()
+    }
+
+    /// Returns all objects selected by a custom selection function.
+    pub fn locate_with_selection_function<F>(
+        &self,
+        function: F,
+    ) -> PackedSelectionIterator<'_, T, F>
+    where
+        F: SelectionFunction<T>,
+    {
+        PackedSelectionIterator::new(self, function)
+    }
+
+    /// Searches for an element at a given position.
+    ///
+    /// If `query_point` is contained by multiple objects in the tree, one of them
+    /// will be returned.
+    pub fn lookup(&self, query_point: &T::Point) -> Option<&T> {
+        self.locate_with_selection_function(SelectAtPoint::new(query_point.clone()))
+            .next()
+    }
+
+    /// Returns all objects (partially) contained in a rectangle.
+    pub fn lookup_in_rectangle(&self, query_rect: &BoundingRect<T::Point>) -> Vec<&T> {
+        self.locate_with_selection_function(SelectInRectangle::new(query_rect.clone()))
+            .collect()
+    }
+
+    /// Returns all objects (partially) contained in a circle.
+    ///
+    /// Note that `radius2` is the circle's squared radius, not the actual radius.
+    pub fn lookup_in_circle(
+        &self,
+        circle_origin: &T::Point,
+        radius2: &<T::Point as PointN>::Scalar,
+    ) -> Vec<&T> {
+        self.locate_with_selection_function(SelectInCircle::new(
+            circle_origin.clone(),
+            radius2.clone(),
+        ))
+        .collect()
+    }
+
+    /// Returns the nearest neighbor of a query point.
+    ///
+    /// Returns `None` if the tree is empty.
+    pub fn nearest_neighbor(&self, query_point: &T::Point) -> Option<&T> {
+        self.nearest_neighbor_iterator(query_point).next()
+    }
+
+    /// Returns the `n` nearest neighbors of a query point, ordered by increasing distance.
+    pub fn nearest_n_neighbors(&self, query_point: &T::Point, n: usize) -> Vec<&T> {
+        self.nearest_neighbor_iterator(query_point)
+            .take(n)
+            .collect()
+    }
+
+    /// Returns all objects of the tree, ordered by their distance to a query point.
+    pub fn nearest_neighbor_iterator(
+        &self,
+        query_point: &T::Point,
+    ) -> PackedNearestNeighborIterator<'_, T> {
+        PackedNearestNeighborIterator::new(self, query_point.clone())
+    }
+
+    fn is_leaf_node(&self, node: usize) -> bool {
+        node < self.num_leaf_nodes
+    }
+
+    fn root(&self) -> Option<usize> {
+        if self.nodes.is_empty() {
+            None
+        } else {
+            Some(self.nodes.len() - 1)
+        }
+    }
+}
+
+fn chunk_mbr<V, I>(mut mbrs: I) -> BoundingRect<V>
+where
+    V: PointN,
+    I: Iterator<Item = BoundingRect<V>>,
+{
+    let mut result = mbrs.next().unwrap();
+    for mbr in mbrs {
+        result.add_rect(&mbr);
+    }
+    result
+}
+
+/// Sorts elements in STR order, such that each consecutive chunk of `node_size`
+/// elements forms a spatially compact group.
+fn str_sort<E, V, F>(elements: &mut [E], node_size: usize, axis: usize, center: &F)
+where
+    V: PointN,
+    F: Fn(&E) -> V,
+{
+    let remaining_axes = V::dimensions() - axis;
+    if elements.len() <= node_size || remaining_axes == 0 {
+        return;
+    }
+    let num_nodes = elements.len().div_ceil(node_size);
+    let num_slices = nth_root_ceil(num_nodes, remaining_axes);
+    let slice_size = num_nodes.div_ceil(num_slices) * node_size;
+    let compare = |l: &E, r: &E| {
+        center(l)
+            .nth(axis)
+            .partial_cmp(center(r).nth(axis))
+            .unwrap_or(Ordering::Equal)
+    };
+    let mut current = 0;
+    while current + slice_size < elements.len() {
+        ::pdqselect::select_by(&mut elements[current..], slice_size, compare);
+        current += slice_size;
+    }
+    for slice in elements.chunks_mut(slice_size) {
+        str_sort(slice, node_size, axis + 1, center);
+    }
+}
+
+/// An iterator over all objects of a `PackedRTree` that are selected by a
+/// `SelectionFunction`.
+///
+/// This `struct` is created by `PackedRTree::locate_with_selection_function`.
+pub struct PackedSelectionIterator<'a, T, F>
+where
+    T: SpatialObject + 'a,
+{
+    tree: &'a PackedRTree<T>,
+    function: F,
+    todo_list: TodoList,
+    objects: Range<usize>,
+}
+
+impl<'a, T, F> PackedSelectionIterator<'a, T, F>
+where
+    T: SpatialObject + 'a,
+    F: SelectionFunction<T>,
+{
+    fn new(tree: &'a PackedRTree<T>, function: F) -> Self {
+        let mut todo_list = TodoList::new();
+        if let Some(root) = tree.root() {
+            if function.should_descend(&tree.nodes[root].mbr) {
+                todo_list.push(root);
+            }
+        }
+        PackedSelectionIterator {
+            tree,
+            function,
+            todo_list,
+            objects: 0..0,
+        }
+    }
+}
+
+impl<'a, T, F> Iterator for PackedSelectionIterator<'a, T, F>
+where
+    T: SpatialObject + 'a,
+    F: SelectionFunction<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            for index in &mut self.objects {
+                let object = &self.tree.objects[index];
+                if self.function.matches(object) {
+                    return Some(object);
+                }
+            }
+            let index = self.todo_list.pop()?;
+            let node = &self.tree.nodes[index];
+            if self.tree.is_leaf_node(index) {
+                self.objects = node.start..node.end;
+            } else {
+                let nodes = &self.tree.nodes;
+                let function = &self.function;
+                self.todo_list.extend(
+                    (node.start..node.end)
+                        .filter(|child| function.should_descend(&nodes[*child].mbr)),
+                );
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PackedEntry {
+    Node(usize),
+    Object(usize),
+}
+
+/// An iterator over the objects of a `PackedRTree`, ordered by their distance to a
+/// query point.
+///
+/// This `struct` is created by `PackedRTree::nearest_neighbor_iterator`.
+pub struct PackedNearestNeighborIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    tree: &'a PackedRTree<T>,
+    query_point: T::Point,
+    nodes: BinaryHeap<DistanceWrapper<PackedEntry, <T::Point as PointN>::Scalar>>,
+}
+
+impl<'a, T> PackedNearestNeighborIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    fn new(tree: &'a PackedRTree<T>, query_point: T::Point) -> Self {
+        let mut result = PackedNearestNeighborIterator {
+            tree,
+            query_point,
+            nodes: BinaryHeap::new(),
+        };
+        if let Some(root) = tree.root() {
+            result.push_node(root);
+        }
+        result
+    }
+
+    fn push_node(&mut self, index: usize) {
+        let distance = self.tree.nodes[index].mbr.min_dist2(&self.query_point);
+        self.nodes.push(DistanceWrapper {
+            node: PackedEntry::Node(index),
+            distance,
+        });
+    }
+}
+
+impl<'a, T> Iterator for PackedNearestNeighborIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        while let Some(current) = self.nodes.pop() {
+            match current.node {
+                PackedEntry::Object(index) => return Some(&self.tree.objects[index]),
+                PackedEntry::Node(index) => {
+                    let node = &self.tree.nodes[index];
+                    if self.tree.is_leaf_node(index) {
+                        for child in node.start..node.end {
+                            let distance = self.tree.objects[child].distance2(&self.query_point);
+                            self.nodes.push(DistanceWrapper {
+                                node: PackedEntry::Object(child),
+                                distance,
+                            });
+                        }
+                    } else {
+                        for child in node.start..node.end {
+                            self.push_node(child);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PackedRTree;
+    use crate::boundingrect::BoundingRect;
+    use crate::point_traits::PointNExtensions;
+    use crate::rtree::RTreeOptions;
+    use crate::testutils::*;
+    use crate::traits::SpatialObject;
+    use cgmath::Point2;
+
+    #[test]
+    fn test_packed_structure() {
+        for &size in &[0, 1, 5, 6, 7, 36, 37, 500, 1297] {
+            let points = random_points_with_seed::<f64>(size, b"Pack your bags!!");
+            let tree = PackedRTree::bulk_load_with_options(
+                RTreeOptions::new().max_size(6),
+                points.clone(),
+            );
+            assert_eq!(tree.size(), size);
+            assert_eq!(tree.iter().count(), size);
+            let mut children = vec![0; tree.nodes.len()];
+            for (index, node) in tree.nodes.iter().enumerate() {
+                assert!(node.end > node.start && node.end - node.start <= 6);
+                if tree.is_leaf_node(index) {
+                    for object in &tree.objects[node.start..node.end] {
+                        assert!(node.mbr.contains_rect(&object.mbr()));
+                    }
+                } else {
+                    for (child, count) in children
+                        .iter_mut()
+                        .enumerate()
+                        .take(node.end)
+                        .skip(node.start)
+                    {
+                        assert!(node.mbr.contains_rect(&tree.nodes[child].mbr));
+                        *count += 1;
+                    }
+                }
+            }
+            // Every node except the root has exactly one parent
+            let num_nodes = tree.nodes.len();
+            for (index, count) in children.iter().enumerate() {
+                assert_eq!(*count, if index + 1 == num_nodes { 0 } else { 1 });
+            }
+            for point in &points {
+                assert_eq!(tree.lookup(point), Some(point));
+            }
+        }
+    }
+
+    #[test]
+    fn test_packed_queries() {
+        let (tree, points) = create_random_tree::<f64>(2000, b"Packed and ready");
+        let packed = PackedRTree::bulk_load(points.clone());
+        assert_eq!(packed.mbr(), tree.mbr());
+        assert_eq!(packed.lookup(&Point2::new(2.0, 2.0)), None);
+
+        let rect = BoundingRect::from_corners(&Point2::new(-0.4, -0.1), &Point2::new(0.3, 0.8));
+        let mut expected = tree.lookup_in_rectangle(&rect);
+        let mut found = packed.lookup_in_rectangle(&rect);
+        expected.sort_by(|l, r| l.lex_compare(r));
+        found.sort_by(|l, r| l.lex_compare(r));
+        assert_eq!(found, expected);
+
+        let origin = Point2::new(0.2, -0.3);
+        let mut expected = tree.lookup_in_circle(&origin, &0.1);
+        let mut found = packed.lookup_in_circle(&origin, &0.1);
+        expected.sort_by(|l, r| l.lex_compare(r));
+        found.sort_by(|l, r| l.lex_compare(r));
+        assert_eq!(found, expected);
+
+        for query in random_points_with_seed::<f64>(100, b"Who is close by?") {
+            assert_eq!(
+                packed.nearest_neighbor(&query),
+                tree.nearest_neighbor(&query)
+            );
+            let distances = |result: Vec<&Point2<f64>>| -> Vec<f64> {
+                result.iter().map(|p| p.distance2(&query)).collect()
+            };
+            assert_eq!(
+                distances(packed.nearest_n_neighbors(&query, 10)),
+                distances(tree.nearest_n_neighbors(&query, 10))
+            );
+        }
+        assert_eq!(
+            packed
+                .nearest_neighbor_iterator(&origin)
+                .map(|p| p.distance2(&origin))
+                .collect::<Vec<_>>(),
+            tree.nearest_neighbor_iterator(&origin)
+                .map(|p| p.distance2(&origin))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_packed_empty() {
+        let tree: PackedRTree<Point2<f32>> = PackedRTree::bulk_load(Vec::new());
+        assert_eq!(tree.size(), 0);
+        assert_eq!(tree.mbr(), None);
+        assert_eq!(tree.nearest_neighbor(&Point2::new(0.0, 0.0)), None);
+        assert!(tree
+            .lookup_in_circle(&Point2::new(0.0, 0.0), &1.0)
+            .is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parallel bulk loading and batch queries, enabled by the `rayon` feature.
+
+use super::{nth_root_ceil, DirectoryNodeData, RTree, RTreeNode, RTreeOptions};
+use crate::boundingrect::BoundingRect;
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+// Subtrees with fewer elements are loaded sequentially
+const SEQUENTIAL_THRESHOLD: usize = 4096;
+
+impl<T> DirectoryNodeData<T>
+where
+    T: SpatialObject + Clone + Send + Sync,
+    T::Point: Send + Sync,
+{
+    fn par_bulk_load_with_depth(
+        options: Arc<RTreeOptions>,
+        elements: &mut [T],
+        depth: usize,
+    ) -> DirectoryNodeData<T> {
+        if depth == 1 || elements.len() < SEQUENTIAL_THRESHOLD {
+            return DirectoryNodeData::bulk_load_with_depth(options, elements, depth);
+        }
+        let n_subtree = options.bulk_load_node_size().pow(depth as u32 - 1);
+        let num_clusters = elements.len().div_ceil(n_subtree);
+        let cluster_num_elements = elements.len().div_ceil(num_clusters);
+
+        let children = Self::par_bulk_load_partition(
+            &options,
+            elements,
+            num_clusters,
+            cluster_num_elements,
+            0,
+            depth - 1,
+        );
+        DirectoryNodeData::new_parent(children, depth, options)
+    }
+
+    /// Parallel version of `bulk_load_partition`, returning the created children.
+    fn par_bulk_load_partition(
+        options: &Arc<RTreeOptions>,
+        elements: &mut [T],
+        num_clusters: usize,
+        cluster_num_elements: usize,
+        axis: usize,
+        child_depth: usize,
+    ) -> Vec<RTreeNode<T>> {
+        let remaining_axes = T::Point::dimensions() - axis;
+        if remaining_axes == 1 {
+            par_create_clusters(elements, cluster_num_elements, axis);
+            return elements
+                .par_chunks_mut(cluster_num_elements)
+                .map(|cluster| {
+                    RTreeNode::DirectoryNode(DirectoryNodeData::par_bulk_load_with_depth(
+                        options.clone(),
+                        cluster,
+                        child_depth,
+                    ))
+                })
+                .collect();
+        }
+
+        let num_slices = nth_root_ceil(num_clusters, remaining_axes);
+        let num_clusters_per_slice = num_clusters.div_ceil(num_slices);
+        let slice_num_elements = num_clusters_per_slice * cluster_num_elements;
+        par_create_clusters(elements, slice_num_elements, axis);
+        elements
+            .par_chunks_mut(slice_num_elements)
+            .flat_map(|slice| {
+                Self::par_bulk_load_partition(
+                    options,
+                    slice,
+                    num_clusters_per_slice,
+                    cluster_num_elements,
+                    axis + 1,
+                    child_depth,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Parallel version of `create_clusters`.
+///
+/// Selects the cluster boundary in the middle of `array`, then partitions both halves
+/// concurrently.
+fn par_create_clusters<T>(array: &mut [T], cluster_size: usize, dimension: usize)
+where
+    T: SpatialObject + Send,
+{
+    let num_clusters = array.len().div_ceil(cluster_size);
+    if num_clusters <= 1 {
+        return;
+    }
+    if array.len() < SEQUENTIAL_THRESHOLD {
+        super::create_clusters(array, cluster_size, dimension);
+        return;
+    }
+    let middle = (num_clusters / 2) * cluster_size;
+    ::pdqselect::select_by(array, middle, |l: &T, r: &T| {
+        l.mbr()
+            .lower()
+            .nth(dimension)
+            .partial_cmp(r.mbr().lower().nth(dimension))
+            .unwrap_or(Ordering::Equal)
+    });
+    let (left, right) = array.split_at_mut(middle);
+    rayon::join(
+        || par_create_clusters(left, cluster_size, dimension),
+        || par_create_clusters(right, cluster_size, dimension),
+    );
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject + Clone + Send + Sync,
+    T::Point: Send + Sync,
+{
+    /// Creates a new rtree with some initial elements, using all available threads.
+    ///
+    /// Creates the same tree as `bulk_load`, but partitions the elements and builds
+    /// independent subtrees in parallel. Requires the `rayon` feature.
+    pub fn par_bulk_load(elements: Vec<T>) -> RTree<T> {
+        Self::par_bulk_load_with_options(Default::default(), elements)
+    }
+
+    /// Parallel version of `bulk_load_with_options`.
+    pub fn par_bulk_load_with_options(options: RTreeOptions, mut elements: Vec<T>) -> RTree<T> {
+        let options = Arc::new(options);
+        let m = options.bulk_load_node_size();
+        let mut depth = 1;
+        while m.pow(depth as u32) < elements.len() {
+            depth += 1;
+        }
+        RTree {
+            root: DirectoryNodeData::par_bulk_load_with_depth(options, &mut elements, depth),
+            size: elements.len(),
+        }
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject + Sync,
+    T::Point: Send + Sync,
+{
+    /// Returns the nearest neighbor of each query point, querying in parallel.
+    ///
+    /// The result at index `i` belongs to `query_points[i]` and is `None` only if
+    /// the tree is empty. Requires the `rayon` feature.
+    pub fn par_nearest_neighbors(&self, query_points: &[T::Point]) -> Vec<Option<&T>> {
+        query_points
+            .par_iter()
+            .map(|point| self.nearest_neighbor(point))
+            .collect()
+    }
+
+    /// Returns all objects (partially) contained in each query rectangle, querying in
+    /// parallel.
+    ///
+    /// The result at index `i` belongs to `query_rects[i]`. Requires the `rayon` feature.
+    pub fn par_lookup_in_rectangles(&self, query_rects: &[BoundingRect<T::Point>]) -> Vec<Vec<&T>> {
+        query_rects
+            .par_iter()
+            .map(|rect| self.lookup_in_rectangle(rect))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::boundingrect::BoundingRect;
+    use crate::rtree::RTree;
+    use crate::testutils::*;
+    use cgmath::Point2;
+
+    #[test]
+    fn test_par_bulk_load() {
+        let points = random_points_with_seed::<f64>(50000, b"Many hands make ");
+        let tree = RTree::par_bulk_load(points.clone());
+        let sequential = RTree::bulk_load(points.clone());
+        assert_eq!(tree.size(), points.len());
+        assert_eq!(tree.root.depth, sequential.root.depth);
+        assert_eq!(tree.mbr(), sequential.mbr());
+        for point in points.iter().step_by(7) {
+            assert_eq!(tree.lookup(point), Some(point));
+        }
+        let tree3d = RTree::par_bulk_load(
+            points
+                .chunks_exact(3)
+                .map(|c| [c[0].x, c[1].x, c[2].y])
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(tree3d.size(), points.len() / 3);
+        assert_eq!(tree3d.iter().count(), tree3d.size());
+    }
+
+    #[test]
+    fn test_par_queries() {
+        let (tree, _) = create_random_tree::<f64>(3000, b"..light work....");
+        let queries = random_points_with_seed::<f64>(200, b"Ask them all at ");
+        let nearest = tree.par_nearest_neighbors(&queries);
+        assert_eq!(nearest.len(), queries.len());
+        for (query, result) in queries.iter().zip(nearest) {
+            assert_eq!(result, tree.nearest_neighbor(query));
+        }
+        let rects: Vec<_> = queries
+            .chunks(2)
+            .map(|c| BoundingRect::from_corners(&c[0], &c[1]))
+            .collect();
+        let found = tree.par_lookup_in_rectangles(&rects);
+        for (rect, result) in rects.iter().zip(found) {
+            assert_eq!(result, tree.lookup_in_rectangle(rect));
+        }
+        let empty: RTree<Point2<f64>> = RTree::new();
+        assert_eq!(empty.par_nearest_neighbors(&queries[..2]), vec![None, None]);
+    }
+}
@@ -0,0 +1,689 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::selection_functions::EntrySelectionIterator;
+use super::split::{quadratic_split, SplitEntry};
+use super::{
+    EntryDistanceIterator, EntryRef, Euclidean, RTreeOptions, SelectAtPoint, SelectInCircle,
+    SelectInRectangle, SelectionFunction, TreeEntry,
+};
+use crate::boundingrect::BoundingRect;
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+use std::sync::Arc;
+
+#[derive(Clone)]
+struct PersistentNode<T>
+where
+    T: SpatialObject,
+{
+    bounding_box: Option<BoundingRect<T::Point>>,
+    children: Vec<PersistentEntry<T>>,
+    depth: usize,
+}
+
+#[derive(Clone)]
+enum PersistentEntry<T>
+where
+    T: SpatialObject,
+{
+    Leaf(T),
+    Node(Arc<PersistentNode<T>>),
+}
+
+impl<T> SplitEntry for PersistentEntry<T>
+where
+    T: SpatialObject,
+{
+    type Point = T::Point;
+
+    fn mbr(&self) -> BoundingRect<T::Point> {
+        match self {
+            PersistentEntry::Leaf(ref t) => t.mbr(),
+            PersistentEntry::Node(ref node) => node.mbr(),
+        }
+    }
+}
+
+impl<T> PersistentEntry<T>
+where
+    T: SpatialObject,
+{
+    fn depth(&self) -> usize {
+        match self {
+            PersistentEntry::Leaf(_) => 0,
+            PersistentEntry::Node(ref node) => node.depth,
+        }
+    }
+}
+
+impl<T> TreeEntry for PersistentEntry<T>
+where
+    T: SpatialObject,
+{
+    type Object = T;
+
+    fn entry(&self) -> EntryRef<'_, Self> {
+        match self {
+            PersistentEntry::Leaf(ref t) => EntryRef::Leaf(t),
+            PersistentEntry::Node(ref node) => {
+                EntryRef::Node(node.bounding_box.as_ref().unwrap(), &node.children)
+            }
+        }
+    }
+}
+
+impl<T> PersistentNode<T>
+where
+    T: SpatialObject,
+{
+    fn new(depth: usize, children: Vec<PersistentEntry<T>>) -> Self {
+        let mut result = PersistentNode {
+            bounding_box: None,
+            children,
+            depth,
+        };
+        result.update_mbr();
+        result
+    }
+
+    fn mbr(&self) -> BoundingRect<T::Point> {
+        self.bounding_box.clone().unwrap()
+    }
+
+    fn update_mbr(&mut self) {
+        let mut mbrs = self.children.iter().map(|child| child.mbr());
+        self.bounding_box = mbrs.next().map(|first| {
+            mbrs.fold(first, |mut result, mbr| {
+                result.add_rect(&mbr);
+                result
+            })
+        });
+    }
+
+    /// Returns the index of the child that requires the least enlargement to include `mbr`.
+    fn choose_subtree(&self, mbr: &BoundingRect<T::Point>) -> usize {
+        let mut best = None;
+        for (index, child) in self.children.iter().enumerate() {
+            let child_mbr = child.mbr();
+            let mut enlarged = child_mbr.clone();
+            enlarged.add_rect(mbr);
+            let area = child_mbr.area();
+            let key = (enlarged.area() - area.clone(), area);
+            match best {
+                Some((ref best_key, _)) if *best_key <= key => {}
+                _ => best = Some((key, index)),
+            }
+        }
+        best.map(|(_, index)| index).unwrap()
+    }
+}
+
+impl<T> PersistentNode<T>
+where
+    T: SpatialObject + Clone,
+{
+    /// Inserts an entry into this subtree, copying all shared nodes on the way.
+    ///
+    /// Returns a new sibling node if this node had to be split.
+    fn insert(
+        node: &mut Arc<PersistentNode<T>>,
+        entry: PersistentEntry<T>,
+        options: &RTreeOptions,
+    ) -> Option<PersistentEntry<T>> {
+        let node = Arc::make_mut(node);
+        if entry.depth() + 1 == node.depth {
+            node.children.push(entry);
+        } else {
+            let index = node.choose_subtree(&entry.mbr());
+            if let PersistentEntry::Node(ref mut child) = node.children[index] {
+                if let Some(sibling) = PersistentNode::insert(child, entry, options) {
+                    node.children.push(sibling);
+                }
+            }
+        }
+        let result = if node.children.len() > options.max_size {
+            let offsplit = quadratic_split(&mut node.children, options.min_size);
+            Some(PersistentEntry::Node(Arc::new(PersistentNode::new(
+                node.depth, offsplit,
+            ))))
+        } else {
+            None
+        };
+        node.update_mbr();
+        result
+    }
+
+    /// Removes the leaf at the end of `path`, copying all shared nodes on the path.
+    ///
+    /// Child nodes that become underfull are dissolved, their remaining entries are
+    /// appended to `orphans`.
+    fn remove_at(
+        node: &mut Arc<PersistentNode<T>>,
+        path: &[usize],
+        min_size: usize,
+        orphans: &mut Vec<PersistentEntry<T>>,
+    ) -> T {
+        let node = Arc::make_mut(node);
+        let index = path[0];
+        let removed = if path.len() == 1 {
+            match node.children.swap_remove(index) {
+                PersistentEntry::Leaf(t) => t,
+                PersistentEntry::Node(_) => panic!("Path must end at a leaf"),
+            }
+        } else {
+            let (removed, underfull) = match node.children[index] {
+                PersistentEntry::Node(ref mut child) => {
+                    let removed = PersistentNode::remove_at(child, &path[1..], min_size, orphans);
+                    (removed, child.children.len() < min_size)
+                }
+                PersistentEntry::Leaf(_) => panic!("Path must not contain leaves"),
+            };
+            if underfull {
+                if let PersistentEntry::Node(child) = node.children.swap_remove(index) {
+                    // The child has just been copied, thus it is not shared
+                    let child = Arc::try_unwrap(child).unwrap_or_else(|child| (*child).clone());
+                    orphans.extend(child.children);
+                }
+            }
+            removed
+        };
+        node.update_mbr();
+        removed
+    }
+}
+
+impl<T> PersistentNode<T>
+where
+    T: SpatialObject + PartialEq,
+{
+    /// Searches for an object and stores the child indices leading to it in `path`.
+    fn find_path(&self, object: &T, mbr: &BoundingRect<T::Point>, path: &mut Vec<usize>) -> bool {
+        for (index, child) in self.children.iter().enumerate() {
+            path.push(index);
+            let found = match child {
+                PersistentEntry::Leaf(ref t) => t == object,
+                PersistentEntry::Node(ref node) => {
+                    node.mbr().contains_rect(mbr) && node.find_path(object, mbr, path)
+                }
+            };
+            if found {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+}
+
+/// A persistent r-tree whose versions share all unchanged nodes.
+///
+/// All directory nodes are reference counted. Inserting or removing an object copies
+/// only the nodes on the path from the root to the modified leaf, if they are shared with
+/// another version. Thus, `snapshot` runs in `O(1)` and returns an independent tree that
+/// stays unaffected by later modifications of the original tree. Snapshots can be sent
+/// to other threads if `T` is `Send` and `Sync`.
+///
+/// Nodes are split with Guttman's quadratic split and no forced reinsertion is performed,
+/// since reinsertion would copy many paths. Copied leaf nodes clone their objects,
+/// consider wrapping large objects in an `Arc`.
+///
+/// # Example
+/// ```
+/// extern crate cgmath;
+/// extern crate spade;
+///
+/// use cgmath::Point2;
+/// use spade::rtree::PersistentRTree;
+///
+/// fn main() {
+///     let mut tree = PersistentRTree::new();
+///     tree.insert(Point2::new(0.0, 0.0));
+///     let snapshot = tree.snapshot();
+///     tree.insert(Point2::new(1.0, 1.0));
+///     tree.remove(&Point2::new(0.0, 0.0));
+///     assert_eq!(snapshot.size(), 1);
+///     assert_eq!(snapshot.nearest_neighbor(&Point2::new(2.0, 2.0)), Some(&Point2::new(0.0, 0.0)));
+///     assert_eq!(tree.nearest_neighbor(&Point2::new(2.0, 2.0)), Some(&Point2::new(1.0, 1.0)));
+/// }
+/// ```
+pub struct PersistentRTree<T>
+where
+    T: SpatialObject,
+{
+    root: Arc<PersistentNode<T>>,
+    size: usize,
+    options: Arc<RTreeOptions>,
+}
+
+impl<T> Clone for PersistentRTree<T>
+where
+    T: SpatialObject,
+{
+    fn clone(&self) -> Self {
+        PersistentRTree {
+            root: self.root.clone(),
+            size: self.size,
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl<T> ::std::fmt::Debug for PersistentRTree<T>
+where
+    T: SpatialObject + ::std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Default for PersistentRTree<T>
+where
+    T: SpatialObject,
+{
+    fn default() -> Self {
+        PersistentRTree::new()
+    }
+}
+
+impl<T> PersistentRTree<T>
+where
+    T: SpatialObject,
+{
+    /// Creates an empty persistent r-tree.
+    pub fn new() -> Self {
+        PersistentRTree::new_with_options(Default::default())
+    }
+
+    /// Creates an empty persistent r-tree with custom options.
+    pub fn new_with_options(options: RTreeOptions) -> Self {
+        PersistentRTree {
+            root: Arc::new(PersistentNode::new(1, Vec::new())),
+            size: 0,
+            options: Arc::new(options),
+        }
+    }
+
+    /// Returns a read-only version of the tree's current state in `O(1)` time.
+    ///
+    /// The snapshot shares all nodes with this tree. It remains unchanged if this
+    /// tree is modified afterwards, and vice versa.
+    pub fn snapshot(&self) -> PersistentRTree<T> {
+        self.clone()
+    }
+
+    /// Returns `true` if both trees share the same root, i.e. they are the same version.
+    pub fn ptr_eq(&self, other: &PersistentRTree<T>) -> bool {
+        Arc::ptr_eq(&self.root, &other.root)
+    }
+
+    /// Returns the number of contained objects.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the tree's minimal bounding box.
+    pub fn mbr(&self) -> Option<BoundingRect<T::Point>> {
+        self.root.bounding_box.clone()
+    }
+
+    /// Returns an iterator over all contained objects.
+    pub fn iter(&self) -> PersistentSelectionIterator<'_, T, super::SelectAll> {
+        self.locate_with_selection_function(super::SelectAll)
+    }
+
+    /// Returns all objects selected by a custom selection function.
+    pub fn locate_with_selection_function<F>(
+        &self,
+        function: F,
+    ) -> PersistentSelectionIterator<'_, T, F>
+    where
+        F: SelectionFunction<T>,
+    {
+        PersistentSelectionIterator::new(&self.root, function)
+    }
+
+    /// Searches for an element at a given position.
+    ///
+    /// If `query_point` is contained by multiple objects in the tree, one of them
+    /// will be returned.
+    pub fn lookup(&self, query_point: &T::Point) -> Option<&T> {
+        self.locate_with_selection_function(SelectAtPoint::new(query_point.clone()))
+            .next()
+    }
+
+    /// Returns all objects (partially) contained in a rectangle.
+    pub fn lookup_in_rectangle(&self, query_rect: &BoundingRect<T::Point>) -> Vec<&T> {
+        self.locate_with_selection_function(SelectInRectangle::new(query_rect.clone()))
+            .collect()
+    }
+
+    /// Returns all objects (partially) contained in a circle.
+    ///
+    /// Note that `radius2` is the circle's squared radius, not the actual radius.
+    pub fn lookup_in_circle(
+        &self,
+        circle_origin: &T::Point,
+        radius2: &<T::Point as PointN>::Scalar,
+    ) -> Vec<&T> {
+        self.locate_with_selection_function(SelectInCircle::new(
+            circle_origin.clone(),
+            radius2.clone(),
+        ))
+        .collect()
+    }
+
+    /// Returns the nearest neighbor of a query point.
+    ///
+    /// Returns `None` if the tree is empty.
+    pub fn nearest_neighbor(&self, query_point: &T::Point) -> Option<&T> {
+        self.nearest_neighbor_iterator(query_point).next()
+    }
+
+    /// Returns the `n` nearest neighbors of a query point, ordered by increasing distance.
+    pub fn nearest_n_neighbors(&self, query_point: &T::Point, n: usize) -> Vec<&T> {
+        self.nearest_neighbor_iterator(query_point)
+            .take(n)
+            .collect()
+    }
+
+    /// Returns all objects of the tree, ordered by their distance to a query point.
+    pub fn nearest_neighbor_iterator(
+        &self,
+        query_point: &T::Point,
+    ) -> PersistentNearestNeighborIterator<'_, T> {
+        PersistentNearestNeighborIterator::new(&self.root, query_point.clone())
+    }
+}
+
+impl<T> PersistentRTree<T>
+where
+    T: SpatialObject + Clone,
+{
+    /// Inserts a new object.
+    ///
+    /// Copies all nodes on the path from the root to the new object's leaf node that
+    /// are shared with other versions of this tree.
+    pub fn insert(&mut self, t: T) {
+        self.insert_entry(PersistentEntry::Leaf(t));
+        self.size += 1;
+    }
+
+    fn insert_entry(&mut self, entry: PersistentEntry<T>) {
+        if let Some(sibling) = PersistentNode::insert(&mut self.root, entry, &self.options) {
+            // The root was split, increase the tree's height
+            let depth = self.root.depth + 1;
+            let old_root = PersistentEntry::Node(self.root.clone());
+            self.root = Arc::new(PersistentNode::new(depth, vec![old_root, sibling]));
+        }
+    }
+}
+
+impl<T> PersistentRTree<T>
+where
+    T: SpatialObject + Clone + PartialEq,
+{
+    /// Removes an object from the tree.
+    ///
+    /// Returns `true` if the object could be removed. If multiple objects are equal
+    /// to `obj`, only one will be removed. Only nodes on the path to the removed object
+    /// and nodes that receive entries of dissolved, underfull nodes are copied.
+    pub fn remove(&mut self, obj: &T) -> bool {
+        let mut path = Vec::new();
+        if self.size == 0 || !self.root.find_path(obj, &obj.mbr(), &mut path) {
+            return false;
+        }
+        let mut orphans = Vec::new();
+        PersistentNode::remove_at(&mut self.root, &path, self.options.min_size, &mut orphans);
+        self.size -= 1;
+
+        // Decrease the tree's height if the root has only one child left
+        while self.root.children.len() == 1 && self.root.depth > 1 {
+            let child = match self.root.children[0] {
+                PersistentEntry::Node(ref child) => child.clone(),
+                PersistentEntry::Leaf(_) => unreachable!(),
+            };
+            self.root = child;
+        }
+        if self.root.children.is_empty() {
+            Arc::make_mut(&mut self.root).depth = 1;
+        }
+        while let Some(orphan) = orphans.pop() {
+            match orphan {
+                PersistentEntry::Node(ref node) if node.depth >= self.root.depth => {
+                    // This subtree is too high to be inserted
+                    orphans.extend(node.children.iter().cloned());
+                }
+                orphan => self.insert_entry(orphan),
+            }
+        }
+        true
+    }
+
+    /// Returns `true` if a given object is contained in this tree.
+    pub fn contains(&self, obj: &T) -> bool {
+        self.root.find_path(obj, &obj.mbr(), &mut Vec::new())
+    }
+}
+
+/// An iterator over all objects of a `PersistentRTree` that are selected by a
+/// `SelectionFunction`.
+///
+/// This `struct` is created by `PersistentRTree::locate_with_selection_function`.
+pub struct PersistentSelectionIterator<'a, T, F>
+where
+    T: SpatialObject + 'a,
+{
+    inner: EntrySelectionIterator<'a, PersistentEntry<T>, F>,
+}
+
+impl<'a, T, F> PersistentSelectionIterator<'a, T, F>
+where
+    T: SpatialObject + 'a,
+    F: SelectionFunction<T>,
+{
+    fn new(root: &'a PersistentNode<T>, function: F) -> Self {
+        PersistentSelectionIterator {
+            inner: EntrySelectionIterator::new(&root.children, function),
+        }
+    }
+}
+
+impl<'a, T, F> Iterator for PersistentSelectionIterator<'a, T, F>
+where
+    T: SpatialObject + 'a,
+    F: SelectionFunction<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+}
+
+/// An iterator over the objects of a `PersistentRTree`, ordered by their distance to a
+/// query point.
+///
+/// This `struct` is created by `PersistentRTree::nearest_neighbor_iterator`.
+pub struct PersistentNearestNeighborIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    inner: EntryDistanceIterator<'a, PersistentEntry<T>, Euclidean>,
+}
+
+impl<'a, T> PersistentNearestNeighborIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    fn new(root: &'a PersistentNode<T>, query_point: T::Point) -> Self {
+        PersistentNearestNeighborIterator {
+            inner: EntryDistanceIterator::new(&root.children, query_point, None, Euclidean),
+        }
+    }
+}
+
+impl<'a, T> Iterator for PersistentNearestNeighborIterator<'a, T>
+where
+    T: SpatialObject + 'a,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|(t, _)| t)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PersistentEntry, PersistentNode, PersistentRTree};
+    use crate::boundingrect::BoundingRect;
+    use crate::point_traits::PointNExtensions;
+    use crate::rtree::RTreeOptions;
+    use crate::testutils::*;
+    use crate::traits::SpatialObject;
+    use cgmath::Point2;
+    use std::sync::Arc;
+
+    fn check_invariants<T: SpatialObject>(
+        node: &PersistentNode<T>,
+        options: &RTreeOptions,
+        is_root: bool,
+    ) -> usize {
+        assert!(node.children.len() <= options.max_size);
+        if !is_root {
+            assert!(node.children.len() >= options.min_size);
+        }
+        let mut size = 0;
+        for child in &node.children {
+            assert_eq!(child.depth() + 1, node.depth);
+            assert!(node.mbr().contains_rect(&super::SplitEntry::mbr(child)));
+            size += match child {
+                PersistentEntry::Node(ref child) => check_invariants(child, options, false),
+                PersistentEntry::Leaf(_) => 1,
+            };
+        }
+        size
+    }
+
+    #[test]
+    fn test_persistent_insert_and_remove() {
+        let points = random_points_with_seed::<f64>(2000, b"Persistence pays");
+        let mut tree = PersistentRTree::new();
+        for point in &points {
+            tree.insert(*point);
+        }
+        assert_eq!(tree.size(), points.len());
+        assert_eq!(
+            check_invariants(&tree.root, &tree.options, true),
+            tree.size()
+        );
+        for point in &points {
+            assert!(tree.contains(point));
+            assert_eq!(tree.lookup(point), Some(point));
+        }
+        let query = Point2::new(0.1, 0.2);
+        let reference = crate::rtree::RTree::bulk_load(points.clone());
+        let distances = |result: Vec<&Point2<f64>>| -> Vec<f64> {
+            result.iter().map(|p| p.distance2(&query)).collect()
+        };
+        assert_eq!(
+            distances(tree.nearest_n_neighbors(&query, 20)),
+            distances(reference.nearest_n_neighbors(&query, 20))
+        );
+        let rect = BoundingRect::from_corners(&Point2::new(-0.3, -0.2), &Point2::new(0.4, 0.1));
+        let mut found = tree.lookup_in_rectangle(&rect);
+        let mut expected = reference.lookup_in_rectangle(&rect);
+        found.sort_by(|l, r| l.lex_compare(r));
+        expected.sort_by(|l, r| l.lex_compare(r));
+        assert_eq!(found, expected);
+
+        for (index, point) in points.iter().enumerate() {
+            assert!(tree.remove(point));
+            assert!(!tree.contains(point));
+            if index % 100 == 0 {
+                assert_eq!(
+                    check_invariants(&tree.root, &tree.options, true),
+                    tree.size()
+                );
+            }
+        }
+        assert_eq!(tree.size(), 0);
+        assert_eq!(tree.mbr(), None);
+        assert!(!tree.remove(&points[0]));
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let points = random_points_with_seed::<f64>(1000, b"Say cheese......");
+        let mut tree = PersistentRTree::new();
+        let mut snapshots = Vec::new();
+        for (index, point) in points.iter().enumerate() {
+            if index % 100 == 0 {
+                snapshots.push(tree.snapshot());
+            }
+            tree.insert(*point);
+        }
+        for point in points.iter().step_by(3) {
+            tree.remove(point);
+        }
+        for (index, snapshot) in snapshots.iter().enumerate() {
+            assert_eq!(snapshot.size(), index * 100);
+            assert_eq!(
+                check_invariants(&snapshot.root, &snapshot.options, true),
+                snapshot.size()
+            );
+            for point in &points[..index * 100] {
+                assert!(snapshot.contains(point));
+            }
+            for point in &points[index * 100..] {
+                assert!(!snapshot.contains(point));
+            }
+        }
+        assert_eq!(tree.iter().count(), tree.size());
+        assert_eq!(
+            check_invariants(&tree.root, &tree.options, true),
+            tree.size()
+        );
+
+        // Snapshots share unchanged nodes
+        let snapshot = tree.snapshot();
+        assert!(snapshot.ptr_eq(&tree));
+        tree.insert(Point2::new(5.0, 5.0));
+        assert!(!snapshot.ptr_eq(&tree));
+        let shared = tree
+            .root
+            .children
+            .iter()
+            .zip(snapshot.root.children.iter())
+            .filter(|(l, r)| match (l, r) {
+                (PersistentEntry::Node(l), PersistentEntry::Node(r)) => Arc::ptr_eq(l, r),
+                _ => false,
+            })
+            .count();
+        assert!(shared > 0);
+    }
+
+    #[test]
+    fn test_snapshot_in_thread() {
+        let mut tree = PersistentRTree::new();
+        for point in random_points_with_seed::<f64>(500, b"Off you go, work") {
+            tree.insert(point);
+        }
+        let snapshot = tree.snapshot();
+        let handle = ::std::thread::spawn(move || {
+            snapshot
+                .lookup_in_circle(&Point2::new(0.0, 0.0), &4.0)
+                .len()
+        });
+        tree.insert(Point2::new(0.0, 0.0));
+        assert_eq!(handle.join().unwrap(), 500);
+        assert_eq!(tree.size(), 501);
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{
+    RTree, RTreeNode, RTreeNodeDistanceWrapper, SelectIntersectingEdge, SelectionIterator,
+};
+use crate::point_traits::PointN;
+use crate::primitives::SimpleEdge;
+use crate::traits::{SpadeFloat, SpatialObject};
+use std::collections::BinaryHeap;
+
+impl<T> RTree<T>
+where
+    T: SpatialObject,
+    <T::Point as PointN>::Scalar: SpadeFloat,
+{
+    /// Returns an iterator over all objects whose minimal bounding box is intersected
+    /// by an edge.
+    ///
+    /// The tree is traversed lazily. Note that only the objects' bounding boxes are
+    /// tested, use `Iterator::filter` to apply an exact intersection test.
+    pub fn intersecting_segment(
+        &self,
+        edge: &SimpleEdge<T::Point>,
+    ) -> SelectionIterator<'_, T, SelectIntersectingEdge<T::Point>> {
+        self.locate_with_selection_function(SelectIntersectingEdge::new(edge.clone()))
+    }
+
+    /// Returns the first object hit by a ray.
+    ///
+    /// The ray consists of all points `origin + t * direction` with `t >= 0`.
+    /// `intersect` performs the exact intersection test for a single object: it must
+    /// return the smallest `t` at which the ray hits the object, or `None` if the object
+    /// is missed. Objects are only tested if their bounding box is hit before the closest
+    /// hit found so far.
+    ///
+    /// Returns the closest object together with the `t` at which it was hit.
+    pub fn raycast<F>(
+        &self,
+        origin: &T::Point,
+        direction: &T::Point,
+        mut intersect: F,
+    ) -> Option<(&T, <T::Point as PointN>::Scalar)>
+    where
+        F: FnMut(&T) -> Option<<T::Point as PointN>::Scalar>,
+    {
+        let mut heap = BinaryHeap::new();
+        let mut best: Option<(&T, <T::Point as PointN>::Scalar)> = None;
+        push_ray_children(&mut heap, &self.root.children, origin, direction);
+        while let Some(RTreeNodeDistanceWrapper { node, distance }) = heap.pop() {
+            if let Some((_, best_t)) = best {
+                if distance >= best_t {
+                    // No remaining object can be hit earlier
+                    break;
+                }
+            }
+            match node {
+                RTreeNode::DirectoryNode(ref data) => {
+                    push_ray_children(&mut heap, &data.children, origin, direction)
+                }
+                RTreeNode::Leaf(ref t) => {
+                    if let Some(hit) = intersect(t) {
+                        if best.map(|(_, best_t)| hit < best_t).unwrap_or(true) {
+                            best = Some((t, hit));
+                        }
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+fn push_ray_children<'a, T>(
+    heap: &mut BinaryHeap<RTreeNodeDistanceWrapper<'a, T>>,
+    children: &'a [RTreeNode<T>],
+    origin: &T::Point,
+    direction: &T::Point,
+) where
+    T: SpatialObject,
+    <T::Point as PointN>::Scalar: SpadeFloat,
+{
+    for child in children {
+        if let Some(entry) = child.mbr().ray_entry(origin, direction) {
+            heap.push(RTreeNodeDistanceWrapper {
+                node: child,
+                distance: entry,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::point_traits::PointNExtensions;
+    use crate::primitives::{SimpleCircle, SimpleEdge};
+    use crate::rtree::RTree;
+    use crate::testutils::*;
+    use crate::traits::SpatialObject;
+    use cgmath::{InnerSpace, Point2, Vector2};
+
+    fn ray_circle_intersection(
+        circle: &SimpleCircle<Point2<f32>>,
+        origin: Point2<f32>,
+        direction: Vector2<f32>,
+    ) -> Option<f32> {
+        let offset = origin - circle.center;
+        let a = direction.magnitude2();
+        let b = 2.0 * direction.dot(offset);
+        let c = offset.magnitude2() - circle.radius * circle.radius;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let t0 = (-b - discriminant.sqrt()) / (2.0 * a);
+        let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+        if t1 < 0.0 {
+            None
+        } else {
+            Some(t0.max(0.0))
+        }
+    }
+
+    #[test]
+    fn test_intersecting_segment() {
+        let edges: Vec<_> = random_points_with_seed::<f32>(1000, b"A bolt from the ")
+            .chunks(2)
+            .map(|ps| SimpleEdge::new(ps[0], ps[0].add(&ps[1].mul(0.1))))
+            .collect();
+        let tree = RTree::bulk_load(edges.clone());
+        let queries = random_points_with_seed::<f32>(20, b"blue, a strike o");
+        for query in queries.chunks(2) {
+            let query = SimpleEdge::new(query[0], query[1]);
+            let expected: Vec<_> = edges
+                .iter()
+                .filter(|e| e.mbr().intersects_edge(&query))
+                .collect();
+            let result: Vec<_> = tree.intersecting_segment(&query).collect();
+            assert_eq!(result.len(), expected.len());
+            for e in expected {
+                assert!(result.contains(&e));
+            }
+        }
+        let miss = SimpleEdge::new(Point2::new(2.0, 2.0), Point2::new(3.0, 5.0));
+        assert_eq!(tree.intersecting_segment(&miss).count(), 0);
+    }
+
+    #[test]
+    fn test_raycast() {
+        let circles: Vec<_> = random_points_with_seed::<f32>(500, b"f lightning, a f")
+            .into_iter()
+            .map(|center| SimpleCircle::new(center, 0.02))
+            .collect();
+        let tree = RTree::bulk_load(circles.clone());
+        let origins = random_points_with_seed::<f32>(50, b"lash of thunder.");
+        let targets = random_points_with_seed::<f32>(50, b"Then silence. Wa");
+        let mut hits = 0;
+        for (origin, target) in origins.iter().zip(targets.iter()) {
+            let direction = target - origin;
+            let expected = circles
+                .iter()
+                .filter_map(|c| ray_circle_intersection(c, *origin, direction))
+                .fold(None, |best: Option<f32>, t| {
+                    Some(best.map(|best| best.min(t)).unwrap_or(t))
+                });
+            let result = tree.raycast(origin, &Point2::new(direction.x, direction.y), |c| {
+                ray_circle_intersection(c, *origin, direction)
+            });
+            assert_eq!(result.map(|(_, t)| t), expected);
+            if let Some((circle, t)) = result {
+                hits += 1;
+                assert_eq!(ray_circle_intersection(circle, *origin, direction), Some(t));
+            }
+        }
+        assert!(hits > 0);
+
+        let empty: RTree<SimpleCircle<Point2<f32>>> = RTree::new();
+        assert!(empty
+            .raycast(&Point2::new(0.0, 0.0), &Point2::new(1.0, 0.0), |_| Some(
+                0.0
+            ))
+            .is_none());
+    }
+}
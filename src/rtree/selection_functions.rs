@@ -0,0 +1,432 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{DirectoryNodeData, EntryRef, RTreeNode, RTreeObjectMut, TreeEntry};
+use crate::boundingrect::BoundingRect;
+use crate::point_traits::PointN;
+use crate::primitives::SimpleEdge;
+use crate::traits::{SpadeFloat, SpatialObject};
+use smallvec::SmallVec;
+
+// Large enough to traverse most trees without allocating
+type TodoList<N> = SmallVec<[N; 24]>;
+
+/// Defines which objects of an r-tree are selected by a query.
+///
+/// A selection function guides the traversal of an r-tree: Subtrees are only
+/// searched if `should_descend` returns `true` for their bounding rectangle, and
+/// a contained object is only returned if `matches` returns `true`.
+/// For correct results, `should_descend` must return `true` for every rectangle
+/// that may contain an object accepted by `matches`.
+///
+/// Use `RTree::locate_with_selection_function` to run a query with a custom selection
+/// function.
+///
+/// # Example
+/// ```
+/// extern crate cgmath;
+/// extern crate spade;
+///
+/// use cgmath::Point2;
+/// use spade::BoundingRect;
+/// use spade::rtree::{RTree, SelectionFunction};
+///
+/// // Selects all points lying on the x axis
+/// struct OnXAxis;
+///
+/// impl SelectionFunction<Point2<f32>> for OnXAxis {
+///     fn should_descend(&self, mbr: &BoundingRect<Point2<f32>>) -> bool {
+///         mbr.lower().y <= 0.0 && mbr.upper().y >= 0.0
+///     }
+///
+///     fn matches(&self, point: &Point2<f32>) -> bool {
+///         point.y == 0.0
+///     }
+/// }
+///
+/// fn main() {
+///     let mut tree = RTree::new();
+///     tree.insert(Point2::new(1.0, 0.0));
+///     tree.insert(Point2::new(1.0, 1.0));
+///     tree.insert(Point2::new(-2.0, 0.0));
+///     assert_eq!(tree.locate_with_selection_function(OnXAxis).count(), 2);
+/// }
+/// ```
+pub trait SelectionFunction<T>
+where
+    T: SpatialObject,
+{
+    /// Returns `true` if the subtree with the given bounding rectangle should be searched.
+    fn should_descend(&self, mbr: &BoundingRect<T::Point>) -> bool;
+
+    /// Returns `true` if the given object is selected.
+    fn matches(&self, object: &T) -> bool;
+}
+
+/// Selects all objects.
+///
+/// Used by `RTree::iter_mut`.
+pub struct SelectAll;
+
+impl<T> SelectionFunction<T> for SelectAll
+where
+    T: SpatialObject,
+{
+    fn should_descend(&self, _: &BoundingRect<T::Point>) -> bool {
+        true
+    }
+
+    fn matches(&self, _: &T) -> bool {
+        true
+    }
+}
+
+/// Selects all objects containing a point.
+///
+/// Used by `RTree::lookup`.
+pub struct SelectAtPoint<V: PointN> {
+    point: V,
+}
+
+impl<V: PointN> SelectAtPoint<V> {
+    /// Creates a selection function for a query point.
+    pub fn new(point: V) -> Self {
+        SelectAtPoint { point }
+    }
+}
+
+impl<T> SelectionFunction<T> for SelectAtPoint<T::Point>
+where
+    T: SpatialObject,
+{
+    fn should_descend(&self, mbr: &BoundingRect<T::Point>) -> bool {
+        mbr.contains_point(&self.point)
+    }
+
+    fn matches(&self, object: &T) -> bool {
+        object.contains(&self.point)
+    }
+}
+
+/// Selects all objects whose bounding rectangle intersects a rectangle.
+///
+/// Used by `RTree::lookup_in_rectangle_iter`.
+pub struct SelectInRectangle<V: PointN> {
+    rect: BoundingRect<V>,
+}
+
+impl<V: PointN> SelectInRectangle<V> {
+    /// Creates a selection function for a query rectangle.
+    pub fn new(rect: BoundingRect<V>) -> Self {
+        SelectInRectangle { rect }
+    }
+}
+
+impl<T> SelectionFunction<T> for SelectInRectangle<T::Point>
+where
+    T: SpatialObject,
+{
+    fn should_descend(&self, mbr: &BoundingRect<T::Point>) -> bool {
+        mbr.intersects(&self.rect)
+    }
+
+    fn matches(&self, object: &T) -> bool {
+        object.mbr().intersects(&self.rect)
+    }
+}
+
+/// Selects all objects (partially) contained in a circle.
+///
+/// Used by `RTree::lookup_in_circle_iter`.
+pub struct SelectInCircle<V: PointN> {
+    origin: V,
+    radius2: V::Scalar,
+}
+
+impl<V: PointN> SelectInCircle<V> {
+    /// Creates a selection function for a circle.
+    ///
+    /// Note that `radius2` is the circle's squared radius, not the actual radius.
+    pub fn new(origin: V, radius2: V::Scalar) -> Self {
+        SelectInCircle { origin, radius2 }
+    }
+}
+
+impl<T> SelectionFunction<T> for SelectInCircle<T::Point>
+where
+    T: SpatialObject,
+{
+    fn should_descend(&self, mbr: &BoundingRect<T::Point>) -> bool {
+        mbr.min_dist2(&self.origin) <= self.radius2
+    }
+
+    fn matches(&self, object: &T) -> bool {
+        object.distance2(&self.origin) < self.radius2
+    }
+}
+
+/// Selects all objects whose bounding rectangle is intersected by an edge.
+///
+/// Used by `RTree::intersecting_segment`.
+pub struct SelectIntersectingEdge<V: PointN> {
+    edge: SimpleEdge<V>,
+}
+
+impl<V: PointN> SelectIntersectingEdge<V> {
+    /// Creates a selection function for a query edge.
+    pub fn new(edge: SimpleEdge<V>) -> Self {
+        SelectIntersectingEdge { edge }
+    }
+}
+
+impl<T> SelectionFunction<T> for SelectIntersectingEdge<T::Point>
+where
+    T: SpatialObject,
+    <T::Point as PointN>::Scalar: SpadeFloat,
+{
+    fn should_descend(&self, mbr: &BoundingRect<T::Point>) -> bool {
+        mbr.intersects_edge(&self.edge)
+    }
+
+    fn matches(&self, object: &T) -> bool {
+        object.mbr().intersects_edge(&self.edge)
+    }
+}
+
+/// An iterator over all objects of an r-tree that are selected by a `SelectionFunction`.
+///
+/// This `struct` is created by the `locate_with_selection_function`, `lookup_in_rectangle_iter`
+/// and `lookup_in_circle_iter` methods on `RTree`. The tree is traversed lazily with
+/// an explicit stack, thus no memory is allocated per found object.
+pub struct SelectionIterator<'a, T, F>
+where
+    T: SpatialObject + 'a,
+{
+    inner: EntrySelectionIterator<'a, RTreeNode<T>, F>,
+}
+
+impl<'a, T, F> SelectionIterator<'a, T, F>
+where
+    T: SpatialObject + 'a,
+    F: SelectionFunction<T>,
+{
+    pub(super) fn new(root: &'a DirectoryNodeData<T>, function: F) -> Self {
+        SelectionIterator {
+            inner: EntrySelectionIterator::new(&root.children, function),
+        }
+    }
+}
+
+impl<'a, T, F> Iterator for SelectionIterator<'a, T, F>
+where
+    T: SpatialObject + 'a,
+    F: SelectionFunction<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+}
+
+/// Traverses the entries of a tree with an explicit stack and yields all objects that are
+/// selected by a `SelectionFunction`.
+pub(super) struct EntrySelectionIterator<'a, E, F> {
+    function: F,
+    todo_list: TodoList<&'a E>,
+}
+
+impl<'a, E, F> EntrySelectionIterator<'a, E, F>
+where
+    E: TreeEntry,
+    F: SelectionFunction<E::Object>,
+{
+    pub(super) fn new(children: &'a [E], function: F) -> Self {
+        let mut result = EntrySelectionIterator {
+            function,
+            todo_list: TodoList::new(),
+        };
+        result.extend_todo_list(children);
+        result
+    }
+
+    fn extend_todo_list(&mut self, children: &'a [E]) {
+        let function = &self.function;
+        self.todo_list
+            .extend(children.iter().filter(|child| match child.entry() {
+                EntryRef::Node(mbr, _) => function.should_descend(mbr),
+                EntryRef::Leaf(_) => true,
+            }));
+    }
+}
+
+impl<'a, E, F> Iterator for EntrySelectionIterator<'a, E, F>
+where
+    E: TreeEntry,
+    F: SelectionFunction<E::Object>,
+{
+    type Item = &'a E::Object;
+
+    fn next(&mut self) -> Option<&'a E::Object> {
+        while let Some(next) = self.todo_list.pop() {
+            match next.entry() {
+                EntryRef::Node(_, children) => self.extend_todo_list(children),
+                EntryRef::Leaf(t) => {
+                    if self.function.matches(t) {
+                        return Some(t);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A mutable iterator over all objects of an r-tree that are selected by a `SelectionFunction`.
+///
+/// This `struct` is created by the `locate_with_selection_function_mut`, `iter_mut`,
+/// `lookup_in_rectangle_mut` and `lookup_in_circle_mut` methods on `RTree`.
+/// See `RTreeObjectMut` for restrictions on how the returned objects may be modified.
+pub struct SelectionIteratorMut<'a, T, F>
+where
+    T: SpatialObject + 'a,
+{
+    function: F,
+    todo_list: TodoList<&'a mut RTreeNode<T>>,
+}
+
+impl<'a, T, F> SelectionIteratorMut<'a, T, F>
+where
+    T: SpatialObject + 'a,
+    F: SelectionFunction<T>,
+{
+    pub(super) fn new(root: &'a mut DirectoryNodeData<T>, function: F) -> Self {
+        let mut result = SelectionIteratorMut {
+            function,
+            todo_list: TodoList::new(),
+        };
+        result.extend_todo_list(&mut root.children);
+        result
+    }
+
+    fn extend_todo_list(&mut self, children: &'a mut [RTreeNode<T>]) {
+        let function = &self.function;
+        self.todo_list
+            .extend(children.iter_mut().filter(|child| match child {
+                RTreeNode::DirectoryNode(ref data) => function.should_descend(&data.mbr()),
+                RTreeNode::Leaf(_) => true,
+            }));
+    }
+}
+
+impl<'a, T, F> SelectionIteratorMut<'a, T, F>
+where
+    T: SpatialObject + 'a,
+    F: SelectionFunction<T>,
+{
+    pub(super) fn next_object(&mut self) -> Option<&'a mut T> {
+        while let Some(next) = self.todo_list.pop() {
+            match next {
+                RTreeNode::DirectoryNode(data) => {
+                    self.extend_todo_list(&mut data.children);
+                }
+                RTreeNode::Leaf(t) => {
+                    if self.function.matches(t) {
+                        return Some(t);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T, F> Iterator for SelectionIteratorMut<'a, T, F>
+where
+    T: SpatialObject + 'a,
+    F: SelectionFunction<T>,
+{
+    type Item = RTreeObjectMut<'a, T>;
+
+    fn next(&mut self) -> Option<RTreeObjectMut<'a, T>> {
+        self.next_object().map(RTreeObjectMut::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SelectionFunction;
+    use crate::boundingrect::BoundingRect;
+    use crate::rtree::RTree;
+    use crate::testutils::*;
+    use crate::HasPosition;
+    use cgmath::Point2;
+
+    struct SelectRightHalf;
+
+    impl<T> SelectionFunction<T> for SelectRightHalf
+    where
+        T: HasPosition<Point = Point2<f32>>,
+    {
+        fn should_descend(&self, mbr: &BoundingRect<Point2<f32>>) -> bool {
+            mbr.upper().x > 0.0
+        }
+
+        fn matches(&self, object: &T) -> bool {
+            object.position().x > 0.0
+        }
+    }
+
+    #[test]
+    fn test_locate_with_selection_function() {
+        let (tree, points) = create_random_tree::<f32>(1000, b"Deep in the fore");
+        let expected: Vec<_> = points.iter().filter(|p| p.x > 0.0).collect();
+        let result: Vec<_> = tree
+            .locate_with_selection_function(SelectRightHalf)
+            .collect();
+        assert_eq!(result.len(), expected.len());
+        for p in &expected {
+            assert!(result.contains(p));
+        }
+    }
+
+    #[test]
+    fn test_locate_with_selection_function_mut() {
+        let points = random_points_with_seed::<f32>(1000, b"a small hut lies");
+        let mut tree = RTree::bulk_load(
+            points
+                .iter()
+                .map(|p| PointWithData {
+                    position: *p,
+                    data: 0,
+                })
+                .collect(),
+        );
+        for mut object in tree.locate_with_selection_function_mut(SelectRightHalf) {
+            object.data += 1;
+        }
+        for object in tree.iter() {
+            let expected = if object.position.x > 0.0 { 1 } else { 0 };
+            assert_eq!(object.data, expected);
+        }
+        assert_eq!(tree.iter().count(), points.len());
+    }
+
+    #[test]
+    fn test_locate_with_selection_function_empty() {
+        let mut tree: RTree<Point2<f32>> = RTree::new();
+        assert!(tree
+            .locate_with_selection_function(SelectRightHalf)
+            .next()
+            .is_none());
+        assert!(tree
+            .locate_with_selection_function_mut(SelectRightHalf)
+            .next()
+            .is_none());
+    }
+}
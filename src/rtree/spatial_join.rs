@@ -0,0 +1,227 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{DirectoryNodeData, RTreeNode};
+use crate::boundingrect::BoundingRect;
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+
+/// An iterator over all pairs of objects from two r-trees that are close to each other.
+///
+/// This `struct` is created by the `spatial_join` and `spatial_join_within_distance`
+/// methods on `RTree`. Both trees are traversed simultaneously, thus pairs of subtrees
+/// that are too far away from each other are pruned as a whole.
+///
+/// The iterator only compares the objects' minimal bounding rectangles. An exact geometric
+/// test can be added with `refine`.
+pub struct SpatialJoinIterator<'a, T, U, F = fn(&T, &U) -> bool>
+where
+    T: SpatialObject + 'a,
+    U: SpatialObject<Point = T::Point> + 'a,
+{
+    todo_list: Vec<(&'a RTreeNode<T>, &'a RTreeNode<U>)>,
+    max_distance2: <T::Point as PointN>::Scalar,
+    refinement: F,
+}
+
+impl<'a, T, U> SpatialJoinIterator<'a, T, U>
+where
+    T: SpatialObject + 'a,
+    U: SpatialObject<Point = T::Point> + 'a,
+{
+    pub(super) fn new(
+        first: &'a DirectoryNodeData<T>,
+        second: &'a DirectoryNodeData<U>,
+        max_distance2: <T::Point as PointN>::Scalar,
+    ) -> Self {
+        let mut result = SpatialJoinIterator {
+            todo_list: Vec::new(),
+            max_distance2,
+            refinement: accept_all as fn(&T, &U) -> bool,
+        };
+        if !first.children.is_empty() && !second.children.is_empty() {
+            result.push_pairs(&first.children, &second.children);
+        }
+        result
+    }
+
+    /// Filters the candidate pairs with an exact geometric test.
+    ///
+    /// Only pairs for which `refinement` returns `true` will be yielded.
+    pub fn refine<F>(self, refinement: F) -> SpatialJoinIterator<'a, T, U, F>
+    where
+        F: FnMut(&T, &U) -> bool,
+    {
+        SpatialJoinIterator {
+            todo_list: self.todo_list,
+            max_distance2: self.max_distance2,
+            refinement,
+        }
+    }
+}
+
+fn accept_all<T, U>(_: &T, _: &U) -> bool {
+    true
+}
+
+impl<'a, T, U, F> SpatialJoinIterator<'a, T, U, F>
+where
+    T: SpatialObject + 'a,
+    U: SpatialObject<Point = T::Point> + 'a,
+{
+    fn is_candidate(
+        &self,
+        first: &BoundingRect<T::Point>,
+        second: &BoundingRect<T::Point>,
+    ) -> bool {
+        first.min_dist2_rect(second) <= self.max_distance2
+    }
+
+    fn push_pairs(&mut self, first: &'a [RTreeNode<T>], second: &'a [RTreeNode<U>]) {
+        for first_child in first {
+            let first_mbr = first_child.mbr();
+            for second_child in second {
+                if self.is_candidate(&first_mbr, &second_child.mbr()) {
+                    self.todo_list.push((first_child, second_child));
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, U, F> Iterator for SpatialJoinIterator<'a, T, U, F>
+where
+    T: SpatialObject + 'a,
+    U: SpatialObject<Point = T::Point> + 'a,
+    F: FnMut(&T, &U) -> bool,
+{
+    type Item = (&'a T, &'a U);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(next) = self.todo_list.pop() {
+            match next {
+                (RTreeNode::Leaf(ref first), RTreeNode::Leaf(ref second)) => {
+                    if (self.refinement)(first, second) {
+                        return Some((first, second));
+                    }
+                }
+                (RTreeNode::DirectoryNode(ref first), RTreeNode::DirectoryNode(ref second)) => {
+                    self.push_pairs(&first.children, &second.children);
+                }
+                (RTreeNode::DirectoryNode(ref first), second) => {
+                    self.push_pairs(&first.children, ::std::slice::from_ref(second));
+                }
+                (first, RTreeNode::DirectoryNode(ref second)) => {
+                    self.push_pairs(::std::slice::from_ref(first), &second.children);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::primitives::{SimpleCircle, SimpleEdge};
+    use crate::rtree::RTree;
+    use crate::testutils::*;
+    use crate::traits::SpatialObject;
+    use cgmath::Point2;
+
+    #[test]
+    fn test_spatial_join() {
+        let points = random_points_with_seed::<f32>(400, b"Across the river");
+        let edges: Vec<_> = random_points_with_seed::<f32>(200, b"and into the tre")
+            .chunks(2)
+            .map(|ps| SimpleEdge::new(ps[0], ps[1]))
+            .collect();
+        let point_tree = RTree::bulk_load(points.clone());
+        let edge_tree = RTree::bulk_load(edges.clone());
+
+        let mut expected = Vec::new();
+        for point in &points {
+            for edge in &edges {
+                if edge.mbr().contains_point(point) {
+                    expected.push((*point, *edge));
+                }
+            }
+        }
+        let result: Vec<_> = point_tree
+            .spatial_join(&edge_tree)
+            .map(|(p, e)| (*p, *e))
+            .collect();
+        assert_eq!(result.len(), expected.len());
+        for pair in &expected {
+            assert!(result.contains(pair));
+        }
+    }
+
+    #[test]
+    fn test_spatial_join_within_distance() {
+        const DISTANCE2: f32 = 0.01;
+        let (first, first_points) = create_random_tree::<f32>(300, b"A lonely travelr");
+        let second_points = random_points_with_seed::<f32>(500, b"searching for so");
+        let second = RTree::bulk_load(second_points.clone());
+
+        let mut expected = 0;
+        for p in &first_points {
+            for q in &second_points {
+                if p.distance2(q) <= DISTANCE2 {
+                    expected += 1;
+                }
+            }
+        }
+        assert!(expected > 0);
+        let mut count = 0;
+        for (p, q) in first.spatial_join_within_distance(&second, DISTANCE2) {
+            assert!(p.distance2(q) <= DISTANCE2);
+            count += 1;
+        }
+        assert_eq!(count, expected);
+    }
+
+    #[test]
+    fn test_spatial_join_refine() {
+        let circles: Vec<_> = random_points_with_seed::<f32>(50, b"ember in the ash")
+            .into_iter()
+            .map(|center| SimpleCircle::new(center, 0.2))
+            .collect();
+        let points = random_points_with_seed::<f32>(500, b"glowing in the d");
+        let circle_tree = RTree::bulk_load(circles.clone());
+        let point_tree = RTree::bulk_load(points.clone());
+
+        let mut expected = 0;
+        for circle in &circles {
+            for point in &points {
+                if circle.contains(point) {
+                    expected += 1;
+                }
+            }
+        }
+        let candidates = circle_tree.spatial_join(&point_tree).count();
+        let mut count = 0;
+        for (circle, point) in circle_tree
+            .spatial_join(&point_tree)
+            .refine(|circle, point| circle.contains(point))
+        {
+            assert!(circle.contains(point));
+            count += 1;
+        }
+        assert_eq!(count, expected);
+        assert!(count < candidates);
+    }
+
+    #[test]
+    fn test_spatial_join_empty() {
+        let empty: RTree<Point2<f32>> = RTree::new();
+        let (tree, _) = create_random_tree::<f32>(10, b"nothing to be fo");
+        assert_eq!(empty.spatial_join(&tree).count(), 0);
+        assert_eq!(tree.spatial_join(&empty).count(), 0);
+        assert_eq!(tree.spatial_join(&tree).count(), 10);
+    }
+}
@@ -0,0 +1,318 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Guttman's node split algorithms.
+//!
+//! The r*-tree's own split is implemented directly by `DirectoryNodeData`.
+
+use super::RTreeNode;
+use crate::boundingrect::BoundingRect;
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+use num::zero;
+
+#[cfg(feature = "serde_serialize")]
+use serde::{Deserialize, Serialize};
+
+/// The algorithm used to split overfull nodes of an r-tree.
+///
+/// The r*-tree split yields the best query performance. Guttman's linear and quadratic
+/// splits are cheaper and can be used to speed up write heavy workloads at the
+/// cost of some query performance.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde_serialize", derive(Serialize, Deserialize))]
+pub enum SplitStrategy {
+    /// The r*-tree split, minimizing overlap and margin along the best axis.
+    #[default]
+    RStar,
+    /// Guttman's quadratic split.
+    Quadratic,
+    /// Guttman's linear split.
+    Linear,
+}
+
+/// An entry of a directory node that is distributed among two nodes by a split.
+pub(super) trait SplitEntry {
+    type Point: PointN;
+
+    fn mbr(&self) -> BoundingRect<Self::Point>;
+}
+
+impl<T> SplitEntry for RTreeNode<T>
+where
+    T: SpatialObject,
+{
+    type Point = T::Point;
+
+    fn mbr(&self) -> BoundingRect<T::Point> {
+        RTreeNode::mbr(self)
+    }
+}
+
+struct Group<N>
+where
+    N: SplitEntry,
+{
+    nodes: Vec<N>,
+    mbr: BoundingRect<N::Point>,
+}
+
+impl<N> Group<N>
+where
+    N: SplitEntry,
+{
+    fn new(seed: N) -> Self {
+        Group {
+            mbr: seed.mbr(),
+            nodes: vec![seed],
+        }
+    }
+
+    fn enlargement(&self, mbr: &BoundingRect<N::Point>) -> <N::Point as PointN>::Scalar {
+        let mut enlarged = self.mbr.clone();
+        enlarged.add_rect(mbr);
+        enlarged.area() - self.mbr.area()
+    }
+
+    fn add(&mut self, node: N) {
+        self.mbr.add_rect(&node.mbr());
+        self.nodes.push(node);
+    }
+}
+
+/// Splits `children` in two groups with Guttman's quadratic algorithm.
+///
+/// The first group remains in `children`, the second group is returned.
+pub(super) fn quadratic_split<N>(children: &mut Vec<N>, min_size: usize) -> Vec<N>
+where
+    N: SplitEntry,
+{
+    let mbrs: Vec<_> = children.iter().map(|child| child.mbr()).collect();
+    // Pick the pair that would waste the most area if put into the same group
+    let mut seeds = (0, 1);
+    let mut max_waste = zero();
+    for (i, first) in mbrs.iter().enumerate() {
+        for (j, second) in mbrs.iter().enumerate().skip(i + 1) {
+            let mut combined = first.clone();
+            combined.add_rect(second);
+            let waste = combined.area() - first.area() - second.area();
+            if waste > max_waste || (i, j) == (0, 1) {
+                max_waste = waste;
+                seeds = (i, j);
+            }
+        }
+    }
+    let (mut first, mut second, mut remaining) = pick_seeds(children, seeds);
+    let min_size = ::std::cmp::min(min_size, (remaining.len() + 2) / 2);
+
+    while !remaining.is_empty() {
+        if fill_up(&mut first, &mut second, &mut remaining, min_size) {
+            break;
+        }
+        // Pick the entry with the largest preference for one of the groups
+        let mut next = 0;
+        let mut max_difference = zero();
+        for (index, node) in remaining.iter().enumerate() {
+            let mbr = node.mbr();
+            let difference = abs_diff(first.enlargement(&mbr), second.enlargement(&mbr));
+            if difference > max_difference || index == 0 {
+                max_difference = difference;
+                next = index;
+            }
+        }
+        let node = remaining.swap_remove(next);
+        add_to_best_group(&mut first, &mut second, node);
+    }
+    *children = first.nodes;
+    second.nodes
+}
+
+/// Splits `children` in two groups with Guttman's linear algorithm.
+///
+/// The first group remains in `children`, the second group is returned.
+pub(super) fn linear_split<N>(children: &mut Vec<N>, min_size: usize) -> Vec<N>
+where
+    N: SplitEntry,
+{
+    let mbrs: Vec<_> = children.iter().map(|child| child.mbr()).collect();
+    // Pick the pair with the greatest normalized separation along any axis
+    let mut seeds = (0, 1);
+    let mut max_separation = zero();
+    for axis in 0..N::Point::dimensions() {
+        let lower = |index: usize| mbrs[index].lower().nth(axis).clone();
+        let upper = |index: usize| mbrs[index].upper().nth(axis).clone();
+        let mut highest_lower = 0;
+        let mut lowest_upper = 0;
+        let mut min = lower(0);
+        let mut max = upper(0);
+        for index in 1..mbrs.len() {
+            if lower(index) > lower(highest_lower) {
+                highest_lower = index;
+            }
+            if upper(index) < upper(lowest_upper) {
+                lowest_upper = index;
+            }
+            if lower(index) < min {
+                min = lower(index);
+            }
+            if upper(index) > max {
+                max = upper(index);
+            }
+        }
+        if highest_lower == lowest_upper {
+            continue;
+        }
+        let width = max - min;
+        let mut separation = lower(highest_lower) - upper(lowest_upper);
+        if width > zero() {
+            separation = separation / width;
+        }
+        if separation > max_separation || axis == 0 {
+            max_separation = separation;
+            seeds = (lowest_upper, highest_lower);
+        }
+    }
+    if seeds.0 > seeds.1 {
+        seeds = (seeds.1, seeds.0);
+    }
+    let (mut first, mut second, mut remaining) = pick_seeds(children, seeds);
+    let min_size = ::std::cmp::min(min_size, (remaining.len() + 2) / 2);
+
+    while !remaining.is_empty() {
+        if fill_up(&mut first, &mut second, &mut remaining, min_size) {
+            break;
+        }
+        let node = remaining.pop().unwrap();
+        add_to_best_group(&mut first, &mut second, node);
+    }
+    *children = first.nodes;
+    second.nodes
+}
+
+fn pick_seeds<N>(
+    children: &mut Vec<N>,
+    (first, second): (usize, usize),
+) -> (Group<N>, Group<N>, Vec<N>)
+where
+    N: SplitEntry,
+{
+    debug_assert!(first < second);
+    let mut remaining = ::std::mem::take(children);
+    // Remove the larger index first to keep the smaller one valid
+    let second = Group::new(remaining.swap_remove(second));
+    let first = Group::new(remaining.swap_remove(first));
+    (first, second, remaining)
+}
+
+/// Assigns all remaining nodes to a group if it would underflow otherwise.
+///
+/// Returns `true` if all remaining nodes have been assigned.
+fn fill_up<N>(
+    first: &mut Group<N>,
+    second: &mut Group<N>,
+    remaining: &mut Vec<N>,
+    min_size: usize,
+) -> bool
+where
+    N: SplitEntry,
+{
+    let target = if first.nodes.len() + remaining.len() <= min_size {
+        first
+    } else if second.nodes.len() + remaining.len() <= min_size {
+        second
+    } else {
+        return false;
+    };
+    for node in remaining.drain(..) {
+        target.add(node);
+    }
+    true
+}
+
+fn add_to_best_group<N>(first: &mut Group<N>, second: &mut Group<N>, node: N)
+where
+    N: SplitEntry,
+{
+    let mbr = node.mbr();
+    let key = |group: &Group<N>| (group.enlargement(&mbr), group.mbr.area(), group.nodes.len());
+    let (first_key, second_key) = (key(first), key(second));
+    let prefer_first = first_key.0 < second_key.0
+        || (first_key.0 == second_key.0
+            && (first_key.1 < second_key.1
+                || (first_key.1 == second_key.1 && first_key.2 <= second_key.2)));
+    if prefer_first {
+        first.add(node);
+    } else {
+        second.add(node);
+    }
+}
+
+fn abs_diff<S: PartialOrd + ::std::ops::Sub<Output = S>>(first: S, second: S) -> S {
+    if first > second {
+        first - second
+    } else {
+        second - first
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SplitStrategy;
+    use crate::rtree::{RTree, RTreeOptions};
+    use crate::testutils::*;
+    use cgmath::Point2;
+
+    fn check_strategy(options: RTreeOptions) {
+        let points = random_points_with_seed::<f64>(2000, b"Divide and conqu");
+        let mut tree = RTree::new_with_options(options);
+        for point in &points {
+            tree.insert(*point);
+        }
+        assert_eq!(tree.size(), points.len());
+        let root = tree.root_node().unwrap();
+        let mut to_visit = vec![root];
+        while let Some(node) = to_visit.pop() {
+            assert!(node.len() <= options.max_size);
+            for child in node.children() {
+                assert_eq!(child.depth() + 1, node.depth());
+                assert!(node.mbr().contains_rect(&child.mbr()));
+                if let crate::rtree::RTreeNodeRef::Directory(directory) = child {
+                    assert!(directory.len() >= options.min_size);
+                    to_visit.push(directory);
+                }
+            }
+        }
+        for point in &points {
+            assert_eq!(tree.lookup(point), Some(point));
+        }
+        let query = Point2::new(0.1, -0.3);
+        let expected = points
+            .iter()
+            .map(|p| (p.x - query.x).powi(2) + (p.y - query.y).powi(2))
+            .fold(f64::INFINITY, f64::min);
+        let nearest = tree.nearest_neighbor(&query).unwrap();
+        assert_eq!(
+            (nearest.x - query.x).powi(2) + (nearest.y - query.y).powi(2),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_split_strategies() {
+        for &strategy in &[
+            SplitStrategy::RStar,
+            SplitStrategy::Quadratic,
+            SplitStrategy::Linear,
+        ] {
+            let options = RTreeOptions::new().split_strategy(strategy);
+            check_strategy(options);
+            check_strategy(options.forced_reinsertion(false));
+            check_strategy(options.max_size(12).min_size(5));
+        }
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright 2017 The Spade Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{DirectoryNodeData, RTree, RTreeNode};
+use crate::boundingrect::BoundingRect;
+use crate::point_traits::PointN;
+use crate::traits::SpatialObject;
+use num::zero;
+use std::fmt;
+
+// Number of objects used as sample queries by `RTree::statistics`
+const DEFAULT_SAMPLE_SIZE: usize = 1000;
+
+/// Statistics of all directory nodes on one level of an r-tree.
+///
+/// Part of `RTreeStatistics`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelStatistics<S> {
+    /// The level's depth. Nodes that directly contain objects have depth 1.
+    pub depth: usize,
+    /// The number of directory nodes on this level.
+    pub node_count: usize,
+    /// The average number of children per node, divided by the maximum node size.
+    pub average_fill_factor: f64,
+    /// The summed area in which sibling nodes of this level overlap each other.
+    pub overlap: S,
+    /// The summed area of all nodes of this level that is not covered by their children.
+    ///
+    /// Only pairwise overlaps of the children are taken into account, thus this is
+    /// an estimate if more than two children overlap in the same area.
+    pub dead_space: S,
+}
+
+/// A report describing the structure and quality of an r-tree.
+///
+/// Returned by `RTree::statistics`. Lower overlap, dead space and node access values
+/// indicate a tree that can be queried faster.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RTreeStatistics<S> {
+    /// The tree's height, the root's depth.
+    pub height: usize,
+    /// The number of contained objects.
+    pub size: usize,
+    /// Statistics of each level, starting at the root.
+    pub levels: Vec<LevelStatistics<S>>,
+    /// The average fill factor of all directory nodes except the root.
+    ///
+    /// Equals the root's fill factor if the root is the only directory node.
+    pub average_fill_factor: f64,
+    /// The overlap area summed over all levels.
+    pub total_overlap: S,
+    /// The dead space summed over all levels.
+    pub total_dead_space: S,
+    /// The average number of directory nodes that a point lookup visits, measured
+    /// for a sample of query points.
+    pub average_node_accesses: f64,
+}
+
+impl<S: fmt::Debug> fmt::Display for RTreeStatistics<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "height: {}, size: {}", self.height, self.size)?;
+        writeln!(f, "average fill factor: {:.3}", self.average_fill_factor)?;
+        writeln!(f, "total overlap: {:?}", self.total_overlap)?;
+        writeln!(f, "total dead space: {:?}", self.total_dead_space)?;
+        writeln!(
+            f,
+            "average node accesses: {:.3}",
+            self.average_node_accesses
+        )?;
+        for level in &self.levels {
+            writeln!(
+                f,
+                "depth {}: {} nodes, fill factor: {:.3}, overlap: {:?}, dead space: {:?}",
+                level.depth,
+                level.node_count,
+                level.average_fill_factor,
+                level.overlap,
+                level.dead_space
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: SpatialObject,
+{
+    /// Returns statistics describing the tree's structure and quality.
+    ///
+    /// The node access estimate is measured by looking up the centers of up to
+    /// 1000 contained objects, evenly spread over all objects. Use
+    /// `statistics_for_queries` to measure with custom query points.
+    /// This method visits every node of the tree.
+    pub fn statistics(&self) -> RTreeStatistics<<T::Point as PointN>::Scalar> {
+        let step = ::std::cmp::max(1, self.size() / DEFAULT_SAMPLE_SIZE);
+        let queries: Vec<_> = self
+            .iter()
+            .step_by(step)
+            .map(|object| object.mbr().center())
+            .collect();
+        self.statistics_for_queries(&queries)
+    }
+
+    /// Returns statistics describing the tree's structure and quality.
+    ///
+    /// The node access estimate is measured by performing point lookups for each of
+    /// the given query points.
+    pub fn statistics_for_queries(
+        &self,
+        queries: &[T::Point],
+    ) -> RTreeStatistics<<T::Point as PointN>::Scalar> {
+        let height = self.root.depth;
+        let mut levels: Vec<_> = (1..=height)
+            .rev()
+            .map(|depth| LevelStatistics {
+                depth,
+                node_count: 0,
+                average_fill_factor: 0.0,
+                overlap: zero(),
+                dead_space: zero(),
+            })
+            .collect();
+        let mut children_per_level = vec![0; height];
+        collect_statistics(&self.root, &mut levels, &mut children_per_level);
+
+        let max_size = self.root.options.max_size as f64;
+        for (level, children) in levels.iter_mut().zip(children_per_level.iter()) {
+            level.average_fill_factor = *children as f64 / (level.node_count as f64 * max_size);
+        }
+        // The root is excluded since its fill factor is not bounded by the minimum size
+        let skip = if levels.len() > 1 { 1 } else { 0 };
+        let node_count: usize = levels[skip..].iter().map(|level| level.node_count).sum();
+        let children: usize = children_per_level[skip..].iter().sum();
+        let average_fill_factor = children as f64 / (node_count as f64 * max_size);
+
+        let node_accesses: usize = queries
+            .iter()
+            .map(|query| count_node_accesses(&self.root, query))
+            .sum();
+        let average_node_accesses = if queries.is_empty() {
+            0.0
+        } else {
+            node_accesses as f64 / queries.len() as f64
+        };
+
+        let mut total_overlap = zero();
+        let mut total_dead_space = zero();
+        for level in &levels {
+            total_overlap = total_overlap + level.overlap.clone();
+            total_dead_space = total_dead_space + level.dead_space.clone();
+        }
+        RTreeStatistics {
+            height,
+            size: self.size(),
+            levels,
+            average_fill_factor,
+            total_overlap,
+            total_dead_space,
+            average_node_accesses,
+        }
+    }
+}
+
+fn collect_statistics<T>(
+    node: &DirectoryNodeData<T>,
+    levels: &mut [LevelStatistics<<T::Point as PointN>::Scalar>],
+    children_per_level: &mut [usize],
+) where
+    T: SpatialObject,
+{
+    // Levels are ordered from the root downwards
+    let index = levels.len() - node.depth;
+    levels[index].node_count += 1;
+    children_per_level[index] += node.children.len();
+    if node.children.is_empty() {
+        return;
+    }
+
+    let child_mbrs: Vec<_> = node.children.iter().map(|child| child.mbr()).collect();
+    let overlap = pairwise_overlap(&child_mbrs);
+    let area_sum = child_mbrs
+        .iter()
+        .fold(zero::<<T::Point as PointN>::Scalar>(), |acc, mbr| {
+            acc + mbr.area()
+        });
+    let covered = area_sum - overlap.clone();
+    let dead_space = node.mbr().area() - covered;
+    if dead_space > zero() {
+        let level = &mut levels[index];
+        level.dead_space = level.dead_space.clone() + dead_space;
+    }
+    if node.depth > 1 {
+        // The children's overlap is attributed to the children's level
+        let level = &mut levels[index + 1];
+        level.overlap = level.overlap.clone() + overlap;
+    }
+
+    for child in &node.children {
+        if let RTreeNode::DirectoryNode(ref data) = child {
+            collect_statistics(data, levels, children_per_level);
+        }
+    }
+}
+
+fn pairwise_overlap<V: PointN>(mbrs: &[BoundingRect<V>]) -> V::Scalar {
+    let mut result = zero();
+    for (index, first) in mbrs.iter().enumerate() {
+        for second in &mbrs[index + 1..] {
+            if first.intersects(second) {
+                result = result + first.intersect(second).area();
+            }
+        }
+    }
+    result
+}
+
+fn count_node_accesses<T>(node: &DirectoryNodeData<T>, query: &T::Point) -> usize
+where
+    T: SpatialObject,
+{
+    let mut result = 1;
+    for child in &node.children {
+        if let RTreeNode::DirectoryNode(ref data) = child {
+            if data.mbr().contains_point(query) {
+                result += count_node_accesses(data, query);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rtree::{RTree, RTreeOptions};
+    use crate::testutils::*;
+    use cgmath::Point2;
+
+    #[test]
+    fn test_statistics() {
+        let (tree, points) = create_random_tree::<f64>(2000, b"How good is my t");
+        let statistics = tree.statistics();
+        assert_eq!(statistics.size, points.len());
+        assert_eq!(statistics.height, statistics.levels.len());
+        assert_eq!(statistics.levels[0].node_count, 1);
+        assert_eq!(statistics.levels[0].depth, statistics.height);
+        assert_eq!(statistics.levels[0].overlap, 0.0);
+        let leaf_level = statistics.levels.last().unwrap();
+        assert_eq!(leaf_level.depth, 1);
+        let max_size = RTreeOptions::default().max_size as f64;
+        let expected = points.len() as f64 / (leaf_level.node_count as f64 * max_size);
+        assert!((leaf_level.average_fill_factor - expected).abs() < 1e-9);
+        assert!(statistics.average_fill_factor > 0.0 && statistics.average_fill_factor <= 1.0);
+        assert!(statistics.average_node_accesses >= statistics.height as f64);
+        let total: f64 = statistics.levels.iter().map(|level| level.overlap).sum();
+        assert_eq!(statistics.total_overlap, total);
+        assert!(statistics.total_dead_space > 0.0);
+
+        // Bulk loading yields less overlap
+        let bulk_loaded = RTree::bulk_load(points.clone()).statistics();
+        assert!(bulk_loaded.total_overlap < statistics.total_overlap);
+        assert!(!format!("{}", bulk_loaded).is_empty());
+    }
+
+    #[test]
+    fn test_statistics_for_queries() {
+        let mut tree = RTree::new();
+        for x in 0..10 {
+            tree.insert(Point2::new(x as f64, 0.0));
+        }
+        assert_eq!(tree.statistics_for_queries(&[]).average_node_accesses, 0.0);
+        let far_away = tree.statistics_for_queries(&[Point2::new(100.0, 100.0)]);
+        assert_eq!(far_away.average_node_accesses, 1.0);
+
+        let empty: RTree<Point2<f64>> = RTree::new();
+        let statistics = empty.statistics();
+        assert_eq!(statistics.size, 0);
+        assert_eq!(statistics.height, 1);
+        assert_eq!(statistics.average_node_accesses, 0.0);
+    }
+}
@@ -8,7 +8,7 @@
 
 #![allow(missing_docs)]
 use crate::rtree::RTree;
-use crate::traits::SpadeNum;
+use crate::traits::{HasPosition, SpadeNum};
 use cgmath::{BaseFloat, BaseNum, Point2};
 use rand::distributions::range::SampleRange;
 use rand::distributions::{Distribution, Range};
@@ -49,3 +49,22 @@ pub fn create_random_tree<S: SpadeNum + BaseFloat + SampleRange>(
     }
     (tree, points)
 }
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PointWithData {
+    pub position: Point2<f32>,
+    pub data: u32,
+}
+
+impl PointWithData {
+    pub fn new(position: Point2<f32>, data: u32) -> Self {
+        PointWithData { position, data }
+    }
+}
+
+impl HasPosition for PointWithData {
+    type Point = Point2<f32>;
+    fn position(&self) -> Point2<f32> {
+        self.position
+    }
+}